@@ -0,0 +1,225 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batch conversions between slices of amplitudes and decibels.
+//!
+//! These functions convert whole buffers, such as a meter history or a
+//! spectrum, at once. They process the buffers in fixed-size chunks with the
+//! branch-free approximations from the `fast` module, so the compiler can
+//! vectorize the loops, and then redo any values that the approximations
+//! can't represent, such as zero or negative infinity, with the exact
+//! conversions.
+//!
+//! Over the range of -140 dB to +40 dB, the results are within
+//! `fast::MAX_ERROR_DECIBELS` of the scalar `From` conversions.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::batch;
+//!
+//! fn main() {
+//!     let amplitudes = [1.0f32, 0.5, 0.0];
+//!     let mut decibels = [0.0f32; 3];
+//!     batch::amplitudes_to_decibels(&amplitudes, &mut decibels);
+//!     assert!(decibels[1] > -6.03 && decibels[1] < -6.01);
+//!     assert_eq!(decibels[2], std::f32::NEG_INFINITY);
+//! }
+//! ```
+
+use fast::{exp2_approx, in_exp2_domain, log2_approx, DECIBELS_PER_OCTAVE, OCTAVES_PER_DECIBEL};
+use {AmplitudeRatio, DecibelRatio, Float};
+
+// The number of values converted together. Eight f32 values fill an AVX
+// register, and smaller vector units handle the chunk in several steps.
+const CHUNK_SIZE: usize = 8;
+
+// The smallest positive normal f32. Inputs are clamped to it so that the
+// approximation stays branch-free; the fix-up pass replaces those values.
+const MIN_POSITIVE: f32 = 1.175_494_4e-38;
+
+#[inline]
+fn amplitude_is_special(amplitude: f32) -> bool {
+    !amplitude.is_normal() || amplitude < 0.0
+}
+
+#[inline]
+fn amplitude_to_decibels_chunk<T: Float>(input: &[T], output: &mut [T]) {
+    let mut special = false;
+    for (&amplitude, decibels) in input.iter().zip(output.iter_mut()) {
+        let amplitude = amplitude.to_f64() as f32;
+        special |= amplitude_is_special(amplitude);
+        let approximation = log2_approx(amplitude.max(MIN_POSITIVE)) * DECIBELS_PER_OCTAVE;
+        *decibels = T::from_f64(approximation as f64);
+    }
+
+    if special {
+        for (&amplitude, decibels) in input.iter().zip(output.iter_mut()) {
+            if amplitude_is_special(amplitude.to_f64() as f32) {
+                *decibels = DecibelRatio::from(AmplitudeRatio(amplitude)).decibel_value();
+            }
+        }
+    }
+}
+
+#[inline]
+fn decibels_to_amplitude_chunk<T: Float>(input: &[T], output: &mut [T]) {
+    let mut special = false;
+    for (&decibels, amplitude) in input.iter().zip(output.iter_mut()) {
+        let octaves = decibels.to_f64() as f32 * OCTAVES_PER_DECIBEL;
+        special |= !in_exp2_domain(octaves);
+        let approximation = exp2_approx(octaves.clamp(-126.0, 127.0));
+        *amplitude = T::from_f64(approximation as f64);
+    }
+
+    if special {
+        for (&decibels, amplitude) in input.iter().zip(output.iter_mut()) {
+            if !in_exp2_domain(decibels.to_f64() as f32 * OCTAVES_PER_DECIBEL) {
+                *amplitude = AmplitudeRatio::from(DecibelRatio(decibels)).amplitude_value();
+            }
+        }
+    }
+}
+
+/// Converts a slice of amplitudes into decibels, writing the results into
+/// `output`.
+///
+/// # Panics
+///
+/// Panics if `input` and `output` have different lengths.
+pub fn amplitudes_to_decibels<T: Float>(input: &[T], output: &mut [T]) {
+    assert_eq!(input.len(), output.len(),
+               "input and output slices must have the same length");
+    for (input, output) in input.chunks(CHUNK_SIZE).zip(output.chunks_mut(CHUNK_SIZE)) {
+        amplitude_to_decibels_chunk(input, output);
+    }
+}
+
+/// Converts a slice of decibels into amplitudes, writing the results into
+/// `output`.
+///
+/// # Panics
+///
+/// Panics if `input` and `output` have different lengths.
+pub fn decibels_to_amplitudes<T: Float>(input: &[T], output: &mut [T]) {
+    assert_eq!(input.len(), output.len(),
+               "input and output slices must have the same length");
+    for (input, output) in input.chunks(CHUNK_SIZE).zip(output.chunks_mut(CHUNK_SIZE)) {
+        decibels_to_amplitude_chunk(input, output);
+    }
+}
+
+/// Converts a slice of amplitudes into decibels in place.
+pub fn amplitudes_to_decibels_in_place<T: Float>(values: &mut [T]) {
+    let mut input = [T::from_f64(0.0); CHUNK_SIZE];
+    for chunk in values.chunks_mut(CHUNK_SIZE) {
+        let input = &mut input[..chunk.len()];
+        input.copy_from_slice(chunk);
+        amplitude_to_decibels_chunk(input, chunk);
+    }
+}
+
+/// Converts a slice of decibels into amplitudes in place.
+pub fn decibels_to_amplitudes_in_place<T: Float>(values: &mut [T]) {
+    let mut input = [T::from_f64(0.0); CHUNK_SIZE];
+    for chunk in values.chunks_mut(CHUNK_SIZE) {
+        let input = &mut input[..chunk.len()];
+        input.copy_from_slice(chunk);
+        decibels_to_amplitude_chunk(input, chunk);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fast::MAX_ERROR_DECIBELS;
+    use std::vec::Vec;
+
+    // Decibel values across the audible range, with the special cases mixed
+    // in at positions that don't line up with the chunks.
+    fn test_decibels() -> Vec<f32> {
+        let mut decibels: Vec<f32> = (0..1801).map(|i| -140.0 + i as f32 * 0.1).collect();
+        decibels.insert(3, f32::NEG_INFINITY);
+        decibels.insert(20, f32::NEG_INFINITY);
+        decibels
+    }
+
+    #[test]
+    fn test_amplitudes_to_decibels_matches_scalar_conversion() {
+        let mut amplitudes: Vec<f32> = test_decibels().iter()
+            .map(|&decibels| AmplitudeRatio::from(DecibelRatio(decibels)).amplitude_value())
+            .collect();
+        amplitudes.push(-1.0);
+        let mut decibels = vec![0.0; amplitudes.len()];
+        amplitudes_to_decibels(&amplitudes, &mut decibels);
+
+        for (&amplitude, &result) in amplitudes.iter().zip(decibels.iter()) {
+            let exact = DecibelRatio::from(AmplitudeRatio(amplitude)).decibel_value();
+            if exact.is_finite() {
+                assert!((result - exact).abs() <= MAX_ERROR_DECIBELS,
+                        "{} should be {} dB but was {} dB", amplitude, exact, result);
+            } else if exact.is_nan() {
+                assert!(result.is_nan());
+            } else {
+                assert_eq!(result, exact);
+            }
+        }
+
+        amplitudes_to_decibels_in_place(&mut amplitudes);
+        assert_eq!(amplitudes.len(), decibels.len());
+        for (&in_place, &result) in amplitudes.iter().zip(decibels.iter()) {
+            assert!(in_place == result || (in_place.is_nan() && result.is_nan()));
+        }
+    }
+
+    #[test]
+    fn test_decibels_to_amplitudes_matches_scalar_conversion() {
+        let mut decibels = test_decibels();
+        decibels.push(1000.0);
+        let mut amplitudes = vec![0.0; decibels.len()];
+        decibels_to_amplitudes(&decibels, &mut amplitudes);
+
+        for (&decibels, &result) in decibels.iter().zip(amplitudes.iter()) {
+            let exact = AmplitudeRatio::from(DecibelRatio(decibels)).amplitude_value();
+            if exact > 0.0 && exact.is_finite() {
+                let error = 20.0 * (result / exact).log10();
+                assert!(error.abs() <= MAX_ERROR_DECIBELS,
+                        "{} dB was off by {} dB", decibels, error);
+            } else {
+                assert_eq!(result, exact);
+            }
+        }
+
+        let mut in_place = decibels.clone();
+        decibels_to_amplitudes_in_place(&mut in_place);
+        assert_eq!(in_place, amplitudes);
+    }
+
+    #[test]
+    fn test_f64_slices() {
+        let decibels = [0.0f64, -6.02059991327962, -20.0, f64::NEG_INFINITY];
+        let mut amplitudes = [0.0f64; 4];
+        decibels_to_amplitudes(&decibels, &mut amplitudes);
+        assert!((amplitudes[0] - 1.0).abs() < 1e-4);
+        assert!((amplitudes[1] - 0.5).abs() < 1e-4);
+        assert!((amplitudes[2] - 0.1).abs() < 1e-4);
+        assert_eq!(amplitudes[3], 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mismatched_lengths_panic() {
+        amplitudes_to_decibels(&[1.0f32, 0.5], &mut [0.0f32]);
+    }
+}
@@ -0,0 +1,333 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The static curves of compressors, expanders and limiters.
+//!
+//! A `GainComputer` maps an input level in dB to the gain that a dynamics
+//! processor applies at that level, from a threshold, a ratio, a knee width
+//! and a makeup gain. It works on levels from a detector, so it doesn't
+//! include attack and release; those belong to whatever smooths its output.
+//!
+//! The four modes change the slope of the curve on one side of the
+//! threshold:
+//!
+//! * `DownwardCompression` divides the slope above the threshold by the
+//!   ratio. With an infinite ratio, this is a limiter.
+//! * `UpwardExpansion` multiplies the slope above the threshold by the ratio.
+//! * `DownwardExpansion` multiplies the slope below the threshold by the
+//!   ratio. With an infinite ratio, this is a gate.
+//! * `UpwardCompression` divides the slope below the threshold by the ratio.
+//!
+//! A knee width of zero gives a hard knee. Otherwise, the curve follows a
+//! quadratic across a knee of that width centred on the threshold, which
+//! joins the two straight parts with a continuous slope.
+//!
+//! Silence, an input of negative infinity, gets no gain from upward
+//! compression, since raising it would take an infinite gain.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::dynamics::{GainComputer, Mode};
+//!
+//! fn main() {
+//!     let compressor = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-20.0), 4.0,
+//!                                        DecibelRatio(0.0));
+//!     // 8 dB over the threshold comes out 2 dB over it.
+//!     assert_eq!(compressor.gain(DecibelRatio(-12.0)), DecibelRatio(-6.0));
+//!     assert_eq!(compressor.output_level(DecibelRatio(-12.0)), DecibelRatio(-18.0));
+//! }
+//! ```
+
+use DecibelRatio;
+
+/// The kind of dynamics processing that a `GainComputer` does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Lowers levels above the threshold.
+    DownwardCompression,
+    /// Raises levels above the threshold.
+    UpwardExpansion,
+    /// Lowers levels below the threshold.
+    DownwardExpansion,
+    /// Raises levels below the threshold.
+    UpwardCompression,
+}
+
+impl Mode {
+    // Whether the mode changes levels above the threshold, rather than below.
+    fn acts_above_threshold(self) -> bool {
+        match self {
+            Mode::DownwardCompression | Mode::UpwardExpansion => true,
+            Mode::DownwardExpansion | Mode::UpwardCompression => false,
+        }
+    }
+}
+
+/// The static curve of a dynamics processor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GainComputer {
+    mode: Mode,
+    threshold: f64,
+    ratio: f64,
+    knee_width: f64,
+    makeup: f64,
+    // The slope of the curve past the threshold, in output dB per input dB.
+    slope: f64,
+}
+
+impl GainComputer {
+    /// Creates a gain computer with no makeup gain. The ratio may be
+    /// infinite, for a limiter or a gate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threshold isn't finite, if the ratio is less than 1, or
+    /// if the knee width is negative or not finite.
+    pub fn new(mode: Mode, threshold: DecibelRatio<f64>, ratio: f64,
+               knee_width: DecibelRatio<f64>) -> GainComputer {
+        let threshold = threshold.decibel_value();
+        let knee_width = knee_width.decibel_value();
+        assert!(threshold.is_finite(), "the threshold must be finite");
+        assert!(ratio >= 1.0, "the ratio must be at least 1");
+        assert!(knee_width >= 0.0 && knee_width.is_finite(), "the knee width must be finite and not negative");
+
+        let slope = match mode {
+            Mode::DownwardCompression | Mode::UpwardCompression => 1.0 / ratio,
+            Mode::UpwardExpansion | Mode::DownwardExpansion => ratio,
+        };
+        GainComputer { mode, threshold, ratio, knee_width, makeup: 0.0, slope }
+    }
+
+    /// Returns the mode.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the threshold.
+    pub fn threshold(&self) -> DecibelRatio<f64> {
+        DecibelRatio(self.threshold)
+    }
+
+    /// Returns the ratio.
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Returns the knee width.
+    pub fn knee_width(&self) -> DecibelRatio<f64> {
+        DecibelRatio(self.knee_width)
+    }
+
+    /// Returns the makeup gain.
+    pub fn makeup(&self) -> DecibelRatio<f64> {
+        DecibelRatio(self.makeup)
+    }
+
+    /// Sets the makeup gain, which is added to the gain at every level.
+    pub fn set_makeup(&mut self, makeup: DecibelRatio<f64>) {
+        self.makeup = makeup.decibel_value();
+    }
+
+    /// Returns the gain to apply at an input level, including the makeup
+    /// gain. The gain is negative where the curve lowers the level. It's
+    /// never positive infinity or NaN, even for silence.
+    pub fn gain(&self, input: DecibelRatio<f64>) -> DecibelRatio<f64> {
+        DecibelRatio(self.curve_gain(input.decibel_value()) + self.makeup)
+    }
+
+    /// Returns the output level for an input level, including the makeup
+    /// gain. Silence stays silent.
+    pub fn output_level(&self, input: DecibelRatio<f64>) -> DecibelRatio<f64> {
+        if input.decibel_value() == f64::NEG_INFINITY {
+            input
+        } else {
+            input + self.gain(input)
+        }
+    }
+
+    /// Returns the transfer curve as pairs of input and output levels, at
+    /// `points` input levels spaced evenly from `min` to `max`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two points.
+    pub fn transfer_curve(&self, min: DecibelRatio<f64>, max: DecibelRatio<f64>, points: usize)
+                          -> impl Iterator<Item = (DecibelRatio<f64>, DecibelRatio<f64>)> {
+        assert!(points >= 2, "the transfer curve must have at least two points");
+        let computer = *self;
+        let min = min.decibel_value();
+        let step = (max.decibel_value() - min) / (points - 1) as f64;
+        (0..points).map(move |i| {
+            let input = DecibelRatio(min + i as f64 * step);
+            (input, computer.output_level(input))
+        })
+    }
+
+    // The gain of the curve without the makeup gain.
+    fn curve_gain(&self, input: f64) -> f64 {
+        if self.slope == 1.0 {
+            return 0.0;
+        }
+        let over = input - self.threshold;
+        let half_knee = self.knee_width / 2.0;
+        if self.mode.acts_above_threshold() {
+            if over <= -half_knee {
+                0.0
+            } else if over < half_knee {
+                (self.slope - 1.0) * (over + half_knee) * (over + half_knee) / (2.0 * self.knee_width)
+            } else {
+                (self.slope - 1.0) * over
+            }
+        } else if over >= half_knee {
+            0.0
+        } else if over > -half_knee {
+            (1.0 - self.slope) * (over - half_knee) * (over - half_knee) / (2.0 * self.knee_width)
+        } else if input == f64::NEG_INFINITY && self.slope < 1.0 {
+            // Upward compression would raise silence by an infinite gain.
+            0.0
+        } else {
+            (self.slope - 1.0) * over
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    fn assert_gain(computer: &GainComputer, input: f64, expected: f64) {
+        let gain = computer.gain(DecibelRatio(input)).decibel_value();
+        assert!((gain - expected).abs() < 1e-9, "the gain at {} is {}, not {}", input, gain, expected);
+    }
+
+    #[test]
+    fn test_hard_knee_compression() {
+        let compressor = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-20.0), 4.0, DecibelRatio(0.0));
+        assert_gain(&compressor, -40.0, 0.0);
+        assert_gain(&compressor, -20.0, 0.0);
+        assert_gain(&compressor, -10.0, -7.5);
+        assert_gain(&compressor, 0.0, -15.0);
+    }
+
+    #[test]
+    fn test_soft_knee_compression() {
+        // Below the knee: y = x. In the knee: y = x + (1/R - 1)(x - T + W/2)^2 / 2W.
+        // Above the knee: y = T + (x - T)/R.
+        let compressor = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-20.0), 4.0, DecibelRatio(10.0));
+        assert_gain(&compressor, -30.0, 0.0);
+        assert_gain(&compressor, -25.0, 0.0);
+        assert_gain(&compressor, -22.0, -0.75 * 9.0 / 20.0);
+        assert_gain(&compressor, -20.0, -0.75 * 25.0 / 20.0);
+        assert_gain(&compressor, -15.0, -3.75);
+        assert_gain(&compressor, 0.0, -15.0);
+    }
+
+    #[test]
+    fn test_limiter() {
+        let limiter = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-1.0), f64::INFINITY, DecibelRatio(0.0));
+        assert_eq!(limiter.output_level(DecibelRatio(6.0)), DecibelRatio(-1.0));
+        assert_eq!(limiter.output_level(DecibelRatio(-3.0)), DecibelRatio(-3.0));
+    }
+
+    #[test]
+    fn test_downward_expansion() {
+        // Above the knee: y = x. In the knee: y = x + (1 - R)(x - T - W/2)^2 / 2W.
+        // Below the knee: y = T + R(x - T).
+        let expander = GainComputer::new(Mode::DownwardExpansion, DecibelRatio(-40.0), 2.0, DecibelRatio(6.0));
+        assert_gain(&expander, -30.0, 0.0);
+        assert_gain(&expander, -37.0, 0.0);
+        assert_gain(&expander, -40.0, -9.0 / 12.0);
+        assert_gain(&expander, -43.0, -3.0);
+        assert_gain(&expander, -50.0, -10.0);
+
+        let gate = GainComputer::new(Mode::DownwardExpansion, DecibelRatio(-60.0), f64::INFINITY, DecibelRatio(0.0));
+        assert_eq!(gate.output_level(DecibelRatio(-70.0)), DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(gate.output_level(DecibelRatio(-50.0)), DecibelRatio(-50.0));
+    }
+
+    #[test]
+    fn test_upward_modes() {
+        let compressor = GainComputer::new(Mode::UpwardCompression, DecibelRatio(-40.0), 2.0, DecibelRatio(0.0));
+        assert_gain(&compressor, -60.0, 10.0);
+        assert_gain(&compressor, -20.0, 0.0);
+        assert_eq!(compressor.output_level(DecibelRatio(f64::NEG_INFINITY)), DecibelRatio(f64::NEG_INFINITY));
+
+        let expander = GainComputer::new(Mode::UpwardExpansion, DecibelRatio(-10.0), 2.0, DecibelRatio(4.0));
+        assert_gain(&expander, -20.0, 0.0);
+        assert_gain(&expander, -10.0, 4.0 / 8.0);
+        assert_gain(&expander, 0.0, 10.0);
+    }
+
+    #[test]
+    fn test_silence_never_gets_an_infinite_boost() {
+        let modes = [Mode::DownwardCompression, Mode::UpwardExpansion, Mode::DownwardExpansion, Mode::UpwardCompression];
+        for &mode in modes.iter() {
+            for &ratio in [1.0, 4.0, f64::INFINITY].iter() {
+                for &knee_width in [0.0, 6.0].iter() {
+                    let mut computer = GainComputer::new(mode, DecibelRatio(-30.0), ratio, DecibelRatio(knee_width));
+                    computer.set_makeup(DecibelRatio(3.0));
+                    let gain = computer.gain(DecibelRatio(f64::NEG_INFINITY)).decibel_value();
+                    assert!(!gain.is_nan() && gain != f64::INFINITY,
+                            "{:?} with a ratio of {} gives silence a gain of {}", mode, ratio, gain);
+                    assert_eq!(computer.output_level(DecibelRatio(f64::NEG_INFINITY)), DecibelRatio(f64::NEG_INFINITY));
+                }
+            }
+        }
+
+        let compressor = GainComputer::new(Mode::UpwardCompression, DecibelRatio(-40.0), 2.0, DecibelRatio(0.0));
+        assert_eq!(compressor.gain(DecibelRatio(f64::NEG_INFINITY)), DecibelRatio(0.0));
+        let gate = GainComputer::new(Mode::DownwardExpansion, DecibelRatio(-60.0), f64::INFINITY, DecibelRatio(0.0));
+        assert_eq!(gate.gain(DecibelRatio(f64::NEG_INFINITY)), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_makeup() {
+        let mut compressor = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-20.0), 2.0, DecibelRatio(0.0));
+        compressor.set_makeup(DecibelRatio(6.0));
+        assert_gain(&compressor, -30.0, 6.0);
+        assert_gain(&compressor, 0.0, -4.0);
+        assert_eq!(compressor.output_level(DecibelRatio(0.0)), DecibelRatio(-4.0));
+    }
+
+    #[test]
+    fn test_soft_knees_are_continuous() {
+        let modes = [Mode::DownwardCompression, Mode::UpwardExpansion, Mode::DownwardExpansion, Mode::UpwardCompression];
+        for &mode in modes.iter() {
+            let computer = GainComputer::new(mode, DecibelRatio(-30.0), 3.0, DecibelRatio(12.0));
+            let mut previous = computer.output_level(DecibelRatio(-60.0)).decibel_value();
+            for i in 1..=6000 {
+                let output = computer.output_level(DecibelRatio(-60.0 + i as f64 * 0.01)).decibel_value();
+                // The slope never goes past the ratio's, so there are no jumps.
+                assert!(output > previous && output - previous <= 0.03 + 1e-9, "{:?} jumps at step {}", mode, i);
+                previous = output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_curve() {
+        let compressor = GainComputer::new(Mode::DownwardCompression, DecibelRatio(-20.0), 4.0, DecibelRatio(0.0));
+        let curve: Vec<_> = compressor.transfer_curve(DecibelRatio(-40.0), DecibelRatio(0.0), 5).collect();
+        assert_eq!(curve, vec![
+            (DecibelRatio(-40.0), DecibelRatio(-40.0)),
+            (DecibelRatio(-30.0), DecibelRatio(-30.0)),
+            (DecibelRatio(-20.0), DecibelRatio(-20.0)),
+            (DecibelRatio(-10.0), DecibelRatio(-17.5)),
+            (DecibelRatio(0.0), DecibelRatio(-15.0)),
+        ]);
+    }
+}
@@ -0,0 +1,169 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adding and averaging levels by their energy.
+
+use core::iter::Sum;
+
+use {AmplitudeRatio, DecibelRatio, Float, PowerRatio};
+
+impl<T: Float> DecibelRatio<T> {
+    /// Returns the level of uncorrelated sources played together: their
+    /// powers add, so two 60 dB sources make 63 dB. An empty set of sources
+    /// is silent, at negative infinity.
+    pub fn energetic_sum<I: IntoIterator<Item = DecibelRatio<T>>>(levels: I) -> DecibelRatio<T> {
+        let power = levels.into_iter()
+            .fold(T::from_f64(0.0), |sum, level| sum + PowerRatio::from(level).power_value());
+        PowerRatio(power).into()
+    }
+
+    /// Returns the mean of the levels' powers, in decibels, or `None` if
+    /// there are no levels. This is the equivalent level of a series of
+    /// equally long readings.
+    pub fn energetic_mean<I: IntoIterator<Item = DecibelRatio<T>>>(levels: I) -> Option<DecibelRatio<T>> {
+        let (power, count) = levels.into_iter().fold((T::from_f64(0.0), 0usize), |(sum, count), level| {
+            (sum + PowerRatio::from(level).power_value(), count + 1)
+        });
+        if count == 0 {
+            None
+        } else {
+            Some(PowerRatio(power / T::from_f64(count as f64)).into())
+        }
+    }
+
+    /// Returns the level of a source measured together with a background,
+    /// by taking the background's power away from the total: a 63 dB total
+    /// over a 60 dB background leaves 60 dB. If the background is at least as
+    /// loud as the total, nothing is left, and this returns negative
+    /// infinity.
+    pub fn energetic_difference(total: DecibelRatio<T>, background: DecibelRatio<T>) -> DecibelRatio<T> {
+        let zero = T::from_f64(0.0);
+        let power = PowerRatio::from(total).power_value() - PowerRatio::from(background).power_value();
+        PowerRatio(if power > zero { power } else { zero }).into()
+    }
+
+    /// Returns the level of identical, in-phase signals played together: their
+    /// amplitudes add, so two 60 dB signals make 66 dB. This only applies to
+    /// coherent signals; use `energetic_sum()` for independent sources.
+    pub fn coherent_sum<I: IntoIterator<Item = DecibelRatio<T>>>(levels: I) -> DecibelRatio<T> {
+        let amplitude = levels.into_iter()
+            .fold(T::from_f64(0.0), |sum, level| sum + AmplitudeRatio::from(level).amplitude_value());
+        AmplitudeRatio(amplitude).into()
+    }
+}
+
+/// The energetic sum of levels, collected with `Iterator::sum()`.
+///
+/// `DecibelRatio` doesn't implement `Sum` itself, since adding decibels can
+/// mean cascading gains or combining sources. Summing into an `EnergeticSum`
+/// makes the choice explicit.
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate decibel;
+///
+/// use decibel::{DecibelRatio, EnergeticSum};
+///
+/// fn main() {
+///     let sources = [DecibelRatio(60.0f64), DecibelRatio(60.0)];
+///     let EnergeticSum(total) = sources.iter().sum();
+///     assert!((total.decibel_value() - 63.0103).abs() < 0.001);
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnergeticSum<T: Copy>(pub DecibelRatio<T>);
+
+impl<T: Copy> EnergeticSum<T> {
+    /// Returns the summed level.
+    #[inline]
+    pub fn decibel_ratio(&self) -> DecibelRatio<T> {
+        self.0
+    }
+}
+
+impl<T: Float> Sum<DecibelRatio<T>> for EnergeticSum<T> {
+    fn sum<I: Iterator<Item = DecibelRatio<T>>>(levels: I) -> EnergeticSum<T> {
+        EnergeticSum(DecibelRatio::energetic_sum(levels))
+    }
+}
+
+impl<'a, T: Float> Sum<&'a DecibelRatio<T>> for EnergeticSum<T> {
+    fn sum<I: Iterator<Item = &'a DecibelRatio<T>>>(levels: I) -> EnergeticSum<T> {
+        EnergeticSum(DecibelRatio::energetic_sum(levels.cloned()))
+    }
+}
+
+impl<T: Float> From<EnergeticSum<T>> for DecibelRatio<T> {
+    #[inline]
+    fn from(sum: EnergeticSum<T>) -> DecibelRatio<T> {
+        sum.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    fn assert_close(actual: DecibelRatio<f64>, expected: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= 0.001,
+                "{:?} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn test_energetic_sum() {
+        assert_close(DecibelRatio::energetic_sum(vec![DecibelRatio(60.0), DecibelRatio(60.0)]), 63.010);
+        assert_close(DecibelRatio::energetic_sum(vec![DecibelRatio(60.0), DecibelRatio(50.0)]), 60.414);
+        assert_close(DecibelRatio::energetic_sum(vec![DecibelRatio(70.0); 10]), 80.0);
+        assert_close(DecibelRatio::energetic_sum(vec![DecibelRatio(60.0), DecibelRatio(f64::NEG_INFINITY)]), 60.0);
+        assert_eq!(DecibelRatio::energetic_sum(Vec::<DecibelRatio<f64>>::new()), DecibelRatio(f64::NEG_INFINITY));
+
+        let sum = DecibelRatio::energetic_sum(vec![DecibelRatio(60.0f32), DecibelRatio(60.0)]);
+        assert!((sum.decibel_value() - 63.0103).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_energetic_mean() {
+        assert_close(DecibelRatio::energetic_mean(vec![DecibelRatio(60.0), DecibelRatio(70.0)]).unwrap(), 67.404);
+        assert_close(DecibelRatio::energetic_mean(vec![DecibelRatio(55.0); 3]).unwrap(), 55.0);
+        assert_eq!(DecibelRatio::energetic_mean(Vec::<DecibelRatio<f64>>::new()), None);
+    }
+
+    #[test]
+    fn test_energetic_difference() {
+        assert_close(DecibelRatio::energetic_difference(DecibelRatio(63.0103), DecibelRatio(60.0)), 60.0);
+        // Correcting for background noise 10 dB down takes off 0.46 dB.
+        assert_close(DecibelRatio::energetic_difference(DecibelRatio(70.0), DecibelRatio(60.0)), 69.542);
+        assert_eq!(DecibelRatio::energetic_difference(DecibelRatio(60.0), DecibelRatio(60.0)),
+                   DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(DecibelRatio::energetic_difference(DecibelRatio(50.0), DecibelRatio(60.0)),
+                   DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_coherent_sum() {
+        assert_close(DecibelRatio::coherent_sum(vec![DecibelRatio(60.0), DecibelRatio(60.0)]), 66.021);
+        assert_close(DecibelRatio::coherent_sum(vec![DecibelRatio(0.0); 4]), 12.041);
+        assert_eq!(DecibelRatio::coherent_sum(Vec::<DecibelRatio<f64>>::new()), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_energetic_sum_adapter() {
+        let levels = [DecibelRatio(60.0), DecibelRatio(60.0), DecibelRatio(60.0)];
+        let by_reference: EnergeticSum<f64> = levels.iter().sum();
+        let by_value: EnergeticSum<f64> = levels.iter().cloned().sum();
+        assert_eq!(by_reference, by_value);
+        assert_close(by_reference.decibel_ratio(), 64.771);
+        assert_close(by_value.into(), 64.771);
+    }
+}
@@ -0,0 +1,168 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fast approximate conversions between amplitudes and decibels.
+//!
+//! The exact conversions call `log10` and `powf`, which can dominate
+//! per-sample gain loops. These approximations work on the bits of an `f32`
+//! instead: the exponent gives the integer part of the base 2 logarithm or
+//! exponential, and a polynomial corrects the fractional part.
+//!
+//! Over the audible range of -140 dB to +40 dB, the results are within
+//! `MAX_ERROR_DECIBELS` of the exact `From` conversions. Values that the
+//! approximations can't represent, such as zero, negative infinity and NaN,
+//! fall back to the exact conversions.
+
+use {AmplitudeRatio, DecibelRatio};
+
+/// The maximum error of the fast conversions, in dB, over the range of
+/// -140 dB to +40 dB.
+pub const MAX_ERROR_DECIBELS: f32 = 0.0002;
+
+// 20·log10(2), to turn a base 2 logarithm into decibels.
+pub(crate) const DECIBELS_PER_OCTAVE: f32 = 6.020_6;
+// log2(10) / 20, to turn decibels into a base 2 exponent.
+pub(crate) const OCTAVES_PER_DECIBEL: f32 = 0.166_096_4;
+
+/// Approximates `log2(x)` for a positive, normal `x`.
+///
+/// The absolute error of the polynomial is below 1.6e-5 over [1, 2), and it
+/// matches at both ends so that the result is continuous across octaves.
+#[inline]
+pub(crate) fn log2_approx(x: f32) -> f32 {
+    let bits = x.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127;
+    let t = f32::from_bits((bits & 0x007f_ffff) | 0x3f80_0000) - 1.0;
+    let fraction = t * (1.441_917
+        + t * (-0.709_096
+        + t * (0.415_604_94
+        + t * (-0.193_574_42
+        + t * 0.045_148_546))));
+    exponent as f32 + fraction
+}
+
+/// Approximates `2^x` for `x` in [-126, 128).
+///
+/// The relative error of the polynomial is below 3.4e-6 over [0, 1), and it
+/// matches at both ends so that the result is continuous across octaves.
+#[inline]
+pub(crate) fn exp2_approx(x: f32) -> f32 {
+    // Round towards negative infinity without a branch, so that loops over
+    // this function can vectorize.
+    let whole = x as i32;
+    let whole = whole - (whole as f32 > x) as i32;
+    let f = x - whole as f32;
+    let fraction = 1.0 + f * (0.693_032_1
+        + f * (0.241_379_76
+        + f * (0.052_032_38
+        + f * 0.013_555_74)));
+    f32::from_bits(((whole + 127) as u32) << 23) * fraction
+}
+
+/// Returns true if `x` can be passed to `exp2_approx`.
+#[inline]
+pub(crate) fn in_exp2_domain(x: f32) -> bool {
+    (-126.0..128.0).contains(&x)
+}
+
+macro_rules! impl_fast_conversions {
+    ($T: ident) => {
+        impl AmplitudeRatio<$T> {
+            /// Converts this amplitude into decibels, using a fast approximation
+            /// that is within `fast::MAX_ERROR_DECIBELS` of the exact conversion
+            /// over the range of -140 dB to +40 dB.
+            #[inline]
+            pub fn to_decibels_fast(self) -> DecibelRatio<$T> {
+                let amplitude = self.amplitude_value() as f32;
+                if !amplitude.is_normal() || amplitude < 0.0 {
+                    return self.into();
+                }
+                DecibelRatio((log2_approx(amplitude) * DECIBELS_PER_OCTAVE) as $T)
+            }
+        }
+
+        impl DecibelRatio<$T> {
+            /// Converts these decibels into an amplitude, using a fast
+            /// approximation that is within `fast::MAX_ERROR_DECIBELS` of the
+            /// exact conversion over the range of -140 dB to +40 dB.
+            #[inline]
+            pub fn to_amplitude_fast(self) -> AmplitudeRatio<$T> {
+                let octaves = self.decibel_value() as f32 * OCTAVES_PER_DECIBEL;
+                if !in_exp2_domain(octaves) {
+                    return self.into();
+                }
+                AmplitudeRatio(exp2_approx(octaves) as $T)
+            }
+        }
+    }
+}
+
+impl_fast_conversions!(f32);
+impl_fast_conversions!(f64);
+
+#[cfg(test)]
+mod test {
+    use super::MAX_ERROR_DECIBELS;
+    use {AmplitudeRatio, DecibelRatio};
+
+    // Steps through -140 dB to +40 dB in increments that don't line up with
+    // the octaves, so that every part of the polynomials is exercised.
+    fn audible_range() -> impl Iterator<Item = f64> {
+        (0..=180_000).map(|i| -140.0 + i as f64 * 0.001)
+    }
+
+    #[test]
+    fn test_to_amplitude_fast_is_within_error_bound() {
+        for decibels in audible_range() {
+            let exact: AmplitudeRatio<f64> = DecibelRatio(decibels).into();
+            let fast = DecibelRatio(decibels as f32).to_amplitude_fast();
+            let error = 20.0 * (fast.amplitude_value() as f64 / exact.amplitude_value()).log10();
+            assert!(error.abs() <= MAX_ERROR_DECIBELS as f64,
+                    "{} dB was off by {} dB", decibels, error);
+        }
+    }
+
+    #[test]
+    fn test_to_decibels_fast_is_within_error_bound() {
+        for decibels in audible_range() {
+            let amplitude: AmplitudeRatio<f64> = DecibelRatio(decibels).into();
+            let exact: DecibelRatio<f64> = amplitude.into();
+            let fast = AmplitudeRatio(amplitude.amplitude_value() as f32).to_decibels_fast();
+            let error = fast.decibel_value() as f64 - exact.decibel_value();
+            assert!(error.abs() <= MAX_ERROR_DECIBELS as f64,
+                    "{} dB was off by {} dB", decibels, error);
+        }
+    }
+
+    #[test]
+    fn test_f64_fast_conversions_are_within_error_bound() {
+        for decibels in audible_range().step_by(97) {
+            let exact: AmplitudeRatio<f64> = DecibelRatio(decibels).into();
+            let fast = DecibelRatio(decibels).to_amplitude_fast();
+            let error = 20.0 * (fast.amplitude_value() / exact.amplitude_value()).log10();
+            assert!(error.abs() <= MAX_ERROR_DECIBELS as f64);
+
+            let fast = exact.to_decibels_fast();
+            assert!((fast.decibel_value() - decibels).abs() <= MAX_ERROR_DECIBELS as f64);
+        }
+    }
+
+    #[test]
+    fn test_fast_conversions_match_exact_special_cases() {
+        assert_eq!(AmplitudeRatio(0.0f32).to_decibels_fast(), DecibelRatio(f32::NEG_INFINITY));
+        assert_eq!(DecibelRatio(f32::NEG_INFINITY).to_amplitude_fast(), AmplitudeRatio(0.0));
+        assert_eq!(AmplitudeRatio(1.0f32).to_decibels_fast(), DecibelRatio(0.0));
+        assert_eq!(DecibelRatio(0.0f32).to_amplitude_fast(), AmplitudeRatio(1.0));
+        assert!(AmplitudeRatio(-1.0f32).to_decibels_fast().decibel_value().is_nan());
+        assert!(DecibelRatio(f32::NAN).to_amplitude_fast().amplitude_value().is_nan());
+    }
+}
@@ -0,0 +1,107 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The floating-point abstraction used by the decibel conversions.
+
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+use math;
+
+/// A floating-point-like number that the decibel conversions can work with.
+///
+/// The crate implements this for `f32` and `f64`. Implement it for your own
+/// number types, such as a wrapper float, a half-precision float or a
+/// fixed-point type, to use them with `AmplitudeRatio`, `DecibelRatio` and
+/// `PowerRatio`.
+///
+/// ## Example
+///
+/// ```rust
+/// extern crate decibel;
+///
+/// use std::ops::{Add, Div, Mul, Neg, Sub};
+/// use decibel::{AmplitudeRatio, DecibelRatio, Float};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+/// struct Sample(f32);
+///
+/// impl Add for Sample { type Output = Sample; fn add(self, o: Sample) -> Sample { Sample(self.0 + o.0) } }
+/// impl Sub for Sample { type Output = Sample; fn sub(self, o: Sample) -> Sample { Sample(self.0 - o.0) } }
+/// impl Mul for Sample { type Output = Sample; fn mul(self, o: Sample) -> Sample { Sample(self.0 * o.0) } }
+/// impl Div for Sample { type Output = Sample; fn div(self, o: Sample) -> Sample { Sample(self.0 / o.0) } }
+/// impl Neg for Sample { type Output = Sample; fn neg(self) -> Sample { Sample(-self.0) } }
+///
+/// impl Float for Sample {
+///     fn from_f64(value: f64) -> Sample { Sample(value as f32) }
+///     fn to_f64(self) -> f64 { self.0 as f64 }
+///     fn log10(self) -> Sample { Sample(self.0.log10()) }
+///     fn powf(self, n: Sample) -> Sample { Sample(self.0.powf(n.0)) }
+///     fn sqrt(self) -> Sample { Sample(self.0.sqrt()) }
+/// }
+///
+/// fn main() {
+///     let result: DecibelRatio<_> = AmplitudeRatio(Sample(0.5)).into();
+///     assert!((result.decibel_value().0 + 6.0206).abs() < 0.001);
+/// }
+/// ```
+pub trait Float: Copy + PartialOrd
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+    + Neg<Output = Self> {
+    /// Converts an `f64` constant into this type, rounding if necessary.
+    fn from_f64(value: f64) -> Self;
+
+    /// Converts this value into an `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Returns the base 10 logarithm of the value.
+    fn log10(self) -> Self;
+
+    /// Raises the value to a floating-point power.
+    fn powf(self, n: Self) -> Self;
+
+    /// Returns the square root of the value.
+    fn sqrt(self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($T: ident, $log10: ident, $powf: ident, $sqrt: ident) => {
+        impl Float for $T {
+            #[inline]
+            fn from_f64(value: f64) -> $T {
+                value as $T
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn log10(self) -> $T {
+                math::$log10(self)
+            }
+
+            #[inline]
+            fn powf(self, n: $T) -> $T {
+                math::$powf(self, n)
+            }
+
+            #[inline]
+            fn sqrt(self) -> $T {
+                math::$sqrt(self)
+            }
+        }
+    }
+}
+
+impl_float!(f32, log10f, powf, sqrtf);
+impl_float!(f64, log10, pow, sqrt);
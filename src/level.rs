@@ -0,0 +1,506 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Levels in decibels relative to a known reference, such as dBFS or dBu.
+//!
+//! A `DecibelRatio` on its own is unitless: -20 dB could be relative to full
+//! scale, to 0.7746 volts or to 20 micropascals. A `Level` carries its
+//! reference in its type, so a dBFS value can't be passed where a dBu value
+//! is expected. Each level converts to and from the physical quantity it
+//! measures, and between references where the conversion is well-defined.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::level::{Dbu, Dbv};
+//!
+//! fn main() {
+//!     // +4 dBu is the nominal level of professional audio equipment.
+//!     let nominal = Dbu::new(4.0);
+//!     let volts = nominal.quantity();
+//!     assert!(volts > 1.227 && volts < 1.229);
+//!
+//!     // The same voltage is around +1.78 dBV.
+//!     let consumer: Dbv<f64> = nominal.into();
+//!     assert!(consumer.decibel_value() > 1.78 && consumer.decibel_value() < 1.79);
+//! }
+//! ```
+//!
+//! # Levels and gains
+//!
+//! A `Level` is absolute, while a `Gain` is relative. Only the operations
+//! that make sense are allowed: applying a gain to a level gives a level, the
+//! difference between two levels is a gain, and gains cascade by adding.
+//! Adding two levels together is meaningless, so it doesn't compile:
+//!
+//! ```rust,compile_fail
+//! extern crate decibel;
+//!
+//! use decibel::level::Dbfs;
+//!
+//! fn main() {
+//!     let sum = Dbfs::new(-20.0) + Dbfs::new(-20.0);
+//! }
+//! ```
+//!
+//! Neither levels nor gains are created from a `DecibelRatio` implicitly; use
+//! `Level::from_ratio()` or `Gain::from_ratio()` to say which one it is.
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::level::{Dbfs, Gain};
+//!
+//! fn main() {
+//!     let input = Dbfs::new(-18.0);
+//!     let staged = input + Gain(6.0) + Gain(-2.0);
+//!     assert_eq!(staged, Dbfs::new(-14.0));
+//!
+//!     let headroom = Dbfs::new(0.0) - staged;
+//!     assert_eq!(headroom, Gain(14.0));
+//! }
+//! ```
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use {AmplitudeRatio, DecibelRatio, Float, PowerRatio};
+
+/// The reference that a `Level` is measured against.
+pub trait Reference {
+    /// The reference value of the measured quantity, in its SI unit.
+    const QUANTITY: f64;
+
+    /// True if the measured quantity is a power quantity, which uses
+    /// 10·log10, rather than a field quantity, which uses 20·log10.
+    const POWER: bool;
+
+    /// The unit symbol of the level, such as "dBu".
+    const SYMBOL: &'static str;
+}
+
+/// Full scale: a normalized amplitude of 1.0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullScaleRef {}
+
+/// 0.7746 volts RMS, the voltage that dissipates 1 mW in 600 Ω.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DbuRef {}
+
+/// 1 volt RMS.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DbvRef {}
+
+/// 1 milliwatt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DbmRef {}
+
+/// 1 watt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DbwRef {}
+
+/// 20 micropascals, the nominal threshold of human hearing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DbSplRef {}
+
+impl Reference for FullScaleRef {
+    const QUANTITY: f64 = 1.0;
+    const POWER: bool = false;
+    const SYMBOL: &'static str = "dBFS";
+}
+
+impl Reference for DbuRef {
+    // sqrt(0.6), which is usually rounded to 0.7746.
+    const QUANTITY: f64 = 0.774_596_669_241_483_4;
+    const POWER: bool = false;
+    const SYMBOL: &'static str = "dBu";
+}
+
+impl Reference for DbvRef {
+    const QUANTITY: f64 = 1.0;
+    const POWER: bool = false;
+    const SYMBOL: &'static str = "dBV";
+}
+
+impl Reference for DbmRef {
+    const QUANTITY: f64 = 0.001;
+    const POWER: bool = true;
+    const SYMBOL: &'static str = "dBm";
+}
+
+impl Reference for DbwRef {
+    const QUANTITY: f64 = 1.0;
+    const POWER: bool = true;
+    const SYMBOL: &'static str = "dBW";
+}
+
+impl Reference for DbSplRef {
+    const QUANTITY: f64 = 0.000_02;
+    const POWER: bool = false;
+    const SYMBOL: &'static str = "dB SPL";
+}
+
+/// A relative change in level, in decibels.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct Gain<T: Copy>(pub T);
+
+impl<T: Copy> Gain<T> {
+    /// Creates a gain from a decibel ratio.
+    #[inline]
+    pub fn from_ratio(ratio: DecibelRatio<T>) -> Gain<T> {
+        Gain(ratio.decibel_value())
+    }
+
+    /// Returns the gain in decibels.
+    #[inline]
+    pub fn decibel_value(&self) -> T {
+        self.0
+    }
+
+    /// Returns the gain as a decibel ratio.
+    #[inline]
+    pub fn ratio(&self) -> DecibelRatio<T> {
+        DecibelRatio(self.0)
+    }
+}
+
+impl<T: Copy + fmt::Display> fmt::Display for Gain<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} dB", self.0)
+    }
+}
+
+/// An absolute level in decibels, relative to the reference `R`.
+pub struct Level<R, T: Copy> {
+    decibels: T,
+    reference: PhantomData<R>,
+}
+
+/// A level relative to full scale, measured from normalized amplitudes.
+pub type Dbfs<T> = Level<FullScaleRef, T>;
+
+/// A level relative to 0.7746 volts, measured from volts.
+pub type Dbu<T> = Level<DbuRef, T>;
+
+/// A level relative to 1 volt, measured from volts.
+pub type Dbv<T> = Level<DbvRef, T>;
+
+/// A level relative to 1 milliwatt, measured from watts.
+pub type Dbm<T> = Level<DbmRef, T>;
+
+/// A level relative to 1 watt, measured from watts.
+pub type Dbw<T> = Level<DbwRef, T>;
+
+/// A sound pressure level relative to 20 micropascals, measured from pascals.
+pub type DbSpl<T> = Level<DbSplRef, T>;
+
+impl<R, T: Copy> Level<R, T> {
+    /// Creates a level from its value in decibels.
+    #[inline]
+    pub fn new(decibels: T) -> Level<R, T> {
+        Level { decibels, reference: PhantomData }
+    }
+
+    /// Creates a level from a decibel ratio relative to the reference.
+    #[inline]
+    pub fn from_ratio(ratio: DecibelRatio<T>) -> Level<R, T> {
+        Level::new(ratio.decibel_value())
+    }
+
+    /// Returns the level in decibels.
+    #[inline]
+    pub fn decibel_value(&self) -> T {
+        self.decibels
+    }
+
+    /// Returns the level as a decibel ratio relative to the reference.
+    #[inline]
+    pub fn ratio(&self) -> DecibelRatio<T> {
+        DecibelRatio(self.decibels)
+    }
+}
+
+impl<R: Reference, T: Float> Level<R, T> {
+    /// Creates a level from a physical quantity in the reference's SI unit:
+    /// a normalized amplitude for dBFS, volts for dBu and dBV, watts for dBm
+    /// and dBW, or pascals for dB SPL.
+    #[inline]
+    pub fn from_quantity(quantity: T) -> Level<R, T> {
+        let ratio = quantity / T::from_f64(R::QUANTITY);
+        if R::POWER {
+            Level::from_ratio(PowerRatio(ratio).into())
+        } else {
+            Level::from_ratio(AmplitudeRatio(ratio).into())
+        }
+    }
+
+    /// Returns the physical quantity of this level, in the reference's SI
+    /// unit.
+    #[inline]
+    pub fn quantity(&self) -> T {
+        let ratio = if R::POWER {
+            PowerRatio::from(self.ratio()).power_value()
+        } else {
+            AmplitudeRatio::from(self.ratio()).amplitude_value()
+        };
+        ratio * T::from_f64(R::QUANTITY)
+    }
+
+    // Re-expresses this level relative to another reference that measures the
+    // same kind of quantity.
+    #[inline]
+    fn rereference<S: Reference>(self) -> Level<S, T> {
+        debug_assert_eq!(R::POWER, S::POWER);
+        let factor = if R::POWER { 10.0 } else { 20.0 };
+        let offset = T::from_f64(R::QUANTITY / S::QUANTITY).log10() * T::from_f64(factor);
+        Level::new(self.decibels + offset)
+    }
+}
+
+macro_rules! impl_rereference {
+    ($From: ident, $To: ident) => {
+        impl<T: Float> From<Level<$From, T>> for Level<$To, T> {
+            #[inline]
+            fn from(level: Level<$From, T>) -> Level<$To, T> {
+                level.rereference()
+            }
+        }
+    }
+}
+
+impl_rereference!(DbuRef, DbvRef);
+impl_rereference!(DbvRef, DbuRef);
+impl_rereference!(DbmRef, DbwRef);
+impl_rereference!(DbwRef, DbmRef);
+
+impl<T: Copy + Add<Output = T>> Add for Gain<T> {
+    type Output = Gain<T>;
+
+    #[inline]
+    fn add(self, other: Gain<T>) -> Gain<T> {
+        Gain(self.0 + other.0)
+    }
+}
+
+impl<T: Copy + Sub<Output = T>> Sub for Gain<T> {
+    type Output = Gain<T>;
+
+    #[inline]
+    fn sub(self, other: Gain<T>) -> Gain<T> {
+        Gain(self.0 - other.0)
+    }
+}
+
+impl<T: Copy + Neg<Output = T>> Neg for Gain<T> {
+    type Output = Gain<T>;
+
+    #[inline]
+    fn neg(self) -> Gain<T> {
+        Gain(-self.0)
+    }
+}
+
+impl<T: Copy + AddAssign> AddAssign for Gain<T> {
+    #[inline]
+    fn add_assign(&mut self, other: Gain<T>) {
+        self.0 += other.0;
+    }
+}
+
+impl<T: Copy + SubAssign> SubAssign for Gain<T> {
+    #[inline]
+    fn sub_assign(&mut self, other: Gain<T>) {
+        self.0 -= other.0;
+    }
+}
+
+impl<T: Copy + Mul<Output = T>> Mul<T> for Gain<T> {
+    type Output = Gain<T>;
+
+    #[inline]
+    fn mul(self, scale: T) -> Gain<T> {
+        Gain(self.0 * scale)
+    }
+}
+
+impl<R, T: Copy + Add<Output = T>> Add<Gain<T>> for Level<R, T> {
+    type Output = Level<R, T>;
+
+    #[inline]
+    fn add(self, gain: Gain<T>) -> Level<R, T> {
+        Level::new(self.decibels + gain.0)
+    }
+}
+
+impl<R, T: Copy + Add<Output = T>> Add<Level<R, T>> for Gain<T> {
+    type Output = Level<R, T>;
+
+    #[inline]
+    fn add(self, level: Level<R, T>) -> Level<R, T> {
+        Level::new(self.0 + level.decibels)
+    }
+}
+
+impl<R, T: Copy + Sub<Output = T>> Sub<Gain<T>> for Level<R, T> {
+    type Output = Level<R, T>;
+
+    #[inline]
+    fn sub(self, gain: Gain<T>) -> Level<R, T> {
+        Level::new(self.decibels - gain.0)
+    }
+}
+
+impl<R, T: Copy + AddAssign> AddAssign<Gain<T>> for Level<R, T> {
+    #[inline]
+    fn add_assign(&mut self, gain: Gain<T>) {
+        self.decibels += gain.0;
+    }
+}
+
+impl<R, T: Copy + SubAssign> SubAssign<Gain<T>> for Level<R, T> {
+    #[inline]
+    fn sub_assign(&mut self, gain: Gain<T>) {
+        self.decibels -= gain.0;
+    }
+}
+
+impl<R, T: Copy + Sub<Output = T>> Sub for Level<R, T> {
+    type Output = Gain<T>;
+
+    #[inline]
+    fn sub(self, other: Level<R, T>) -> Gain<T> {
+        Gain(self.decibels - other.decibels)
+    }
+}
+
+impl<R, T: Copy> Clone for Level<R, T> {
+    #[inline]
+    fn clone(&self) -> Level<R, T> {
+        *self
+    }
+}
+
+impl<R, T: Copy> Copy for Level<R, T> {}
+
+impl<R, T: Copy + PartialEq> PartialEq for Level<R, T> {
+    #[inline]
+    fn eq(&self, other: &Level<R, T>) -> bool {
+        self.decibels == other.decibels
+    }
+}
+
+impl<R, T: Copy + PartialOrd> PartialOrd for Level<R, T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Level<R, T>) -> Option<Ordering> {
+        self.decibels.partial_cmp(&other.decibels)
+    }
+}
+
+impl<R: Reference, T: Copy + fmt::Debug> fmt::Debug for Level<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Level({:?} {})", self.decibels, R::SYMBOL)
+    }
+}
+
+impl<R: Reference, T: Copy + fmt::Display> fmt::Display for Level<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.decibels, R::SYMBOL)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::ToString;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= 0.001,
+                "{} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn test_levels_convert_to_and_from_quantities() {
+        assert_close(Dbfs::from_quantity(0.5).decibel_value(), -6.02059991327962);
+        assert_close(Dbfs::new(-6.02059991327962).quantity(), 0.5);
+
+        assert_close(Dbu::from_quantity(0.7746).decibel_value(), 0.0);
+        assert_close(Dbu::new(4.0).quantity(), 1.2277);
+        assert_close(Dbv::from_quantity(1.0).decibel_value(), 0.0);
+        assert_close(Dbv::new(-10.0).quantity(), 0.3162);
+
+        // Power levels use 10·log10.
+        assert_close(Dbm::from_quantity(1.0).decibel_value(), 30.0);
+        assert_close(Dbm::new(3.0).quantity(), 0.001995);
+        assert_close(Dbw::from_quantity(0.5).decibel_value(), -3.0103);
+
+        // A pressure of 1 Pa is around 94 dB SPL.
+        assert_close(DbSpl::from_quantity(1.0).decibel_value(), 93.9794);
+        assert_close(DbSpl::new(0.0).quantity(), 0.00002);
+
+        let quiet: Dbfs<f32> = Dbfs::from_quantity(0.0);
+        assert_eq!(quiet.decibel_value(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_compatible_references_convert() {
+        let dbv: Dbv<f64> = Dbu::new(0.0).into();
+        assert_close(dbv.decibel_value(), -2.2185);
+        let dbu: Dbu<f64> = Dbv::new(0.0).into();
+        assert_close(dbu.decibel_value(), 2.2185);
+
+        let dbw: Dbw<f64> = Dbm::new(30.0).into();
+        assert_close(dbw.decibel_value(), 0.0);
+        let dbm: Dbm<f64> = Dbw::new(-10.0).into();
+        assert_close(dbm.decibel_value(), 20.0);
+
+        // Converting keeps the physical quantity.
+        let level = Dbu::new(4.0);
+        let converted: Dbv<f64> = level.into();
+        assert_close(converted.quantity(), level.quantity());
+    }
+
+    #[test]
+    fn test_levels_wrap_decibel_ratios() {
+        let level: Dbfs<f64> = Level::from_ratio(DecibelRatio(-12.0));
+        assert_eq!(level.ratio(), DecibelRatio(-12.0));
+        assert!(level < Dbfs::new(-6.0));
+        assert_eq!(level.to_string(), "-12 dBFS");
+        assert_eq!(format!("{:?}", DbSpl::new(94.0)), "Level(94.0 dB SPL)");
+    }
+
+    #[test]
+    fn test_levels_and_gains_follow_affine_rules() {
+        let mut level = Dbfs::new(-20.0);
+        assert_eq!(level + Gain(6.0), Dbfs::new(-14.0));
+        assert_eq!(Gain(6.0) + level, Dbfs::new(-14.0));
+        assert_eq!(level - Gain(6.0), Dbfs::new(-26.0));
+        assert_eq!(Dbfs::new(-14.0) - level, Gain(6.0));
+
+        level += Gain(3.0);
+        level -= Gain(1.0);
+        assert_eq!(level, Dbfs::new(-18.0));
+
+        let mut gain = Gain(3.0) + Gain(-1.0) - Gain(0.5);
+        assert_eq!(gain, Gain(1.5));
+        gain += Gain(0.5);
+        gain -= Gain(1.0);
+        assert_eq!(-gain, Gain(-1.0));
+        assert_eq!(gain * 2.0, Gain(2.0));
+
+        assert_eq!(Gain::from_ratio(DecibelRatio(4.0)).ratio(), DecibelRatio(4.0));
+        assert_eq!(Gain(-3.5).to_string(), "-3.5 dB");
+    }
+}
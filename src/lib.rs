@@ -67,8 +67,163 @@
 //!
 //! To scale our audio by 10dB, we need to scale each sample by approximately
 //! 3.162 times.
+//!
+//! The conversions work with `f32` and `f64` out of the box, and with any
+//! other number type that implements the `Float` trait.
+//!
+//! # Converting power values
+//!
+//! Signal power, energy and intensity are power quantities, which use
+//! 10·log10 instead of the 20·log10 used for amplitudes. Wrap them in a
+//! `PowerRatio` so that the right conversion is always applied.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::{AmplitudeRatio, DecibelRatio, PowerRatio};
+//!
+//! fn main() {
+//!     // Halving the power should be close to -3 dB.
+//!     let result: DecibelRatio<_> = PowerRatio(0.5).into();
+//!     let expected_decibels = -3.01029995663981;
+//!     assert!(result.decibel_value() >= expected_decibels - 0.001
+//!          && result.decibel_value() <= expected_decibels + 0.001);
+//!
+//!     // Power is the square of amplitude.
+//!     let amplitude: AmplitudeRatio<_> = PowerRatio(0.25).into();
+//!     assert_eq!(amplitude.amplitude_value(), 0.5);
+//! }
+//! ```
+//!
+//! # Fast approximate conversions
+//!
+//! `AmplitudeRatio::to_decibels_fast()` and `DecibelRatio::to_amplitude_fast()`
+//! trade a small, bounded error for speed in per-sample loops. See the `fast`
+//! module for the error bound.
+//!
+//! # Converting whole buffers
+//!
+//! The `batch` module converts slices of amplitudes or decibels at once, for
+//! things like meter histories and spectra.
+//!
+//! # Adding levels
+//!
+//! Uncorrelated sources add by their power, so two 60 dB sources make
+//! 63 dB. `DecibelRatio::energetic_sum()`, `energetic_mean()` and
+//! `energetic_difference()` add, average and subtract levels this way, and
+//! summing an iterator into an `EnergeticSum` does the same. Identical,
+//! in-phase signals add by their amplitude instead, with
+//! `DecibelRatio::coherent_sum()`.
+//!
+//! # Smoothing gain changes
+//!
+//! The `smoothing` module turns target gains in decibels, such as fader
+//! moves, into smooth per-sample `AmplitudeRatio` gains, with linear-in-dB
+//! ramps or one-pole smoothing. The `taper` module maps fader and knob
+//! positions to gains and back, with linear-in-dB, audio and console-style
+//! curves.
+//!
+//! # Dynamics
+//!
+//! The `dynamics` module has the static curves of compressors, limiters,
+//! expanders and gates, which map a detected level in dB to the gain to
+//! apply, with hard or soft knees and makeup gain.
+//!
+//! # Levels with a reference
+//!
+//! The `level` module has typed levels such as dBFS, dBu, dBV, dBm, dBW and
+//! dB SPL, which carry their reference and convert to and from the physical
+//! quantities they measure. Relative changes are `Gain` values, so that only
+//! meaningful arithmetic between levels and gains compiles.
+//!
+//! # Integer PCM samples
+//!
+//! The `pcm` module converts 8-, 16-, 24- and 32-bit integer samples to and
+//! from dBFS, with an explicit choice of full-scale convention.
+//!
+//! # Metering
+//!
+//! The `meter` module has sample-peak, true-peak and RMS meters, which
+//! measure blocks of samples and report their levels as `DecibelRatio` values
+//! in dBFS. The `loudness` module measures loudness in LUFS, following ITU-R
+//! BS.1770-4 and EBU R128, and loudness range (LRA) in LU, following EBU
+//! Tech 3342. The `weighting` module has the A-, C- and Z-weightings from
+//! IEC 61672-1, as filters and as closed-form magnitude responses, and the
+//! `sound_level` module has a calibrated sound level meter that reports
+//! levels such as LAF, LAeq and LCpeak in dB SPL. The `statistics` module
+//! collects series of readings into histograms, for exceedance levels such
+//! as LA10 and LA90 and for energetic and arithmetic means.
+//!
+//! # Normalizing buffers
+//!
+//! The `normalize` module brings buffers to a target sample peak, true peak
+//! or integrated loudness, optionally under a true-peak ceiling, and reports
+//! the measured level, the gain it applied and the resulting peak. The
+//! `replaygain` module works out ReplayGain 2.0 track and album gains and
+//! formats them as tags. The `opus` module converts gains to and from the
+//! Q7.8 fixed point used by Opus headers and R128 tags, and the `mp3` module
+//! encodes and decodes ID3v2 `RVA2` frames and the ReplayGain fields of the
+//! LAME tag.
+//!
+//! # Features
+//!
+//! The `std` feature is enabled by default. To use the crate in `no_std`
+//! builds, disable default features and enable the `libm` feature, which
+//! provides the math functions that would otherwise come from std. When both
+//! are enabled, libm is used for the conversions. The modules that need to
+//! allocate, such as `meter`, are only available with `std`.
 
 #![warn(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("decibel needs a math backend: enable either the `std` or the `libm` feature");
+
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+#[cfg(feature = "libm")]
+extern crate libm;
+
+#[cfg(feature = "std")]
+mod biquad;
+mod energetic;
+mod float;
+mod math;
+
+pub mod batch;
+pub mod dynamics;
+pub mod fast;
+pub mod level;
+#[cfg(feature = "std")]
+pub mod loudness;
+#[cfg(feature = "std")]
+pub mod meter;
+#[cfg(feature = "std")]
+pub mod mp3;
+#[cfg(feature = "std")]
+pub mod normalize;
+pub mod opus;
+pub mod pcm;
+#[cfg(feature = "std")]
+pub mod replaygain;
+pub mod smoothing;
+#[cfg(feature = "std")]
+pub mod sound_level;
+#[cfg(feature = "std")]
+pub mod statistics;
+pub mod taper;
+#[cfg(feature = "std")]
+pub mod weighting;
+
+pub use energetic::EnergeticSum;
+pub use float::Float;
+
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 /// An amplitude value.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -78,34 +233,55 @@ pub struct AmplitudeRatio<T: Copy>(pub T);
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DecibelRatio<T: Copy>(pub T);
 
+/// A power value, such as signal power, energy or intensity.
+///
+/// Power quantities are proportional to the square of field quantities, so
+/// they convert to and from decibels with 10·log10 rather than 20·log10.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PowerRatio<T: Copy>(pub T);
 
-macro_rules! impl_from_amplitude_ratio {
-    ($T: ty) => {        
-        impl From<AmplitudeRatio<$T>> for DecibelRatio<$T> {
-            #[inline]
-            fn from(amplitude: AmplitudeRatio<$T>) -> DecibelRatio<$T> {
-                DecibelRatio(<$T>::log10(amplitude.amplitude_value()) * 20.0)
-            }
-        }                    
+impl<T: Float> From<AmplitudeRatio<T>> for DecibelRatio<T> {
+    #[inline]
+    fn from(amplitude: AmplitudeRatio<T>) -> DecibelRatio<T> {
+        DecibelRatio(amplitude.amplitude_value().log10() * T::from_f64(20.0))
     }
 }
 
-impl_from_amplitude_ratio!(f32);
-impl_from_amplitude_ratio!(f64);
+impl<T: Float> From<DecibelRatio<T>> for AmplitudeRatio<T> {
+    #[inline]
+    fn from(decibels: DecibelRatio<T>) -> AmplitudeRatio<T> {
+        AmplitudeRatio(T::from_f64(10.0).powf(decibels.decibel_value() / T::from_f64(20.0)))
+    }
+}
 
-macro_rules! impl_from_decibel_ratio {
-    ($T: ty) => {        
-        impl From<DecibelRatio<$T>> for AmplitudeRatio<$T> {
-            #[inline]
-            fn from(decibels: DecibelRatio<$T>) -> AmplitudeRatio<$T> {
-                AmplitudeRatio(<$T>::powf(10.0, decibels.decibel_value() / 20.0))
-            }
-        }                    
+impl<T: Float> From<PowerRatio<T>> for DecibelRatio<T> {
+    #[inline]
+    fn from(power: PowerRatio<T>) -> DecibelRatio<T> {
+        DecibelRatio(power.power_value().log10() * T::from_f64(10.0))
     }
 }
 
-impl_from_decibel_ratio!(f32);
-impl_from_decibel_ratio!(f64);
+impl<T: Float> From<DecibelRatio<T>> for PowerRatio<T> {
+    #[inline]
+    fn from(decibels: DecibelRatio<T>) -> PowerRatio<T> {
+        PowerRatio(T::from_f64(10.0).powf(decibels.decibel_value() / T::from_f64(10.0)))
+    }
+}
+
+impl<T: Float> From<PowerRatio<T>> for AmplitudeRatio<T> {
+    #[inline]
+    fn from(power: PowerRatio<T>) -> AmplitudeRatio<T> {
+        AmplitudeRatio(power.power_value().sqrt())
+    }
+}
+
+impl<T: Float> From<AmplitudeRatio<T>> for PowerRatio<T> {
+    #[inline]
+    fn from(amplitude: AmplitudeRatio<T>) -> PowerRatio<T> {
+        let amplitude = amplitude.amplitude_value();
+        PowerRatio(amplitude * amplitude)
+    }
+}
 
 impl<T: Copy> AmplitudeRatio<T> {
     /// Returns the wrapped amplitude value.
@@ -123,11 +299,160 @@ impl<T: Copy> DecibelRatio<T> {
     }
 }
 
+impl<T: Copy> PowerRatio<T> {
+    /// Returns the wrapped power value.
+    #[inline]
+    pub fn power_value(&self) -> T {
+        self.0
+    }
+}
+
+// Decibels are logarithmic, so cascading gains adds them together, while the
+// equivalent amplitude and power ratios multiply.
+
+impl<T: Copy + Add<Output = T>> Add for DecibelRatio<T> {
+    type Output = DecibelRatio<T>;
+
+    #[inline]
+    fn add(self, other: DecibelRatio<T>) -> DecibelRatio<T> {
+        DecibelRatio(self.0 + other.0)
+    }
+}
+
+impl<T: Copy + Sub<Output = T>> Sub for DecibelRatio<T> {
+    type Output = DecibelRatio<T>;
+
+    #[inline]
+    fn sub(self, other: DecibelRatio<T>) -> DecibelRatio<T> {
+        DecibelRatio(self.0 - other.0)
+    }
+}
+
+impl<T: Copy + Neg<Output = T>> Neg for DecibelRatio<T> {
+    type Output = DecibelRatio<T>;
+
+    #[inline]
+    fn neg(self) -> DecibelRatio<T> {
+        DecibelRatio(-self.0)
+    }
+}
+
+impl<T: Copy + AddAssign> AddAssign for DecibelRatio<T> {
+    #[inline]
+    fn add_assign(&mut self, other: DecibelRatio<T>) {
+        self.0 += other.0;
+    }
+}
+
+impl<T: Copy + SubAssign> SubAssign for DecibelRatio<T> {
+    #[inline]
+    fn sub_assign(&mut self, other: DecibelRatio<T>) {
+        self.0 -= other.0;
+    }
+}
+
+impl<T: Copy + Mul<Output = T>> Mul<T> for DecibelRatio<T> {
+    type Output = DecibelRatio<T>;
+
+    #[inline]
+    fn mul(self, scale: T) -> DecibelRatio<T> {
+        DecibelRatio(self.0 * scale)
+    }
+}
+
+impl<T: Copy + Div<Output = T>> Div<T> for DecibelRatio<T> {
+    type Output = DecibelRatio<T>;
+
+    #[inline]
+    fn div(self, scale: T) -> DecibelRatio<T> {
+        DecibelRatio(self.0 / scale)
+    }
+}
+
+macro_rules! impl_linear_ratio_ops {
+    ($Ratio: ident) => {
+        impl<T: Copy + Mul<Output = T>> Mul for $Ratio<T> {
+            type Output = $Ratio<T>;
+
+            #[inline]
+            fn mul(self, other: $Ratio<T>) -> $Ratio<T> {
+                $Ratio(self.0 * other.0)
+            }
+        }
+
+        impl<T: Copy + Div<Output = T>> Div for $Ratio<T> {
+            type Output = $Ratio<T>;
+
+            #[inline]
+            fn div(self, other: $Ratio<T>) -> $Ratio<T> {
+                $Ratio(self.0 / other.0)
+            }
+        }
+
+        impl<T: Copy + MulAssign> MulAssign for $Ratio<T> {
+            #[inline]
+            fn mul_assign(&mut self, other: $Ratio<T>) {
+                self.0 *= other.0;
+            }
+        }
+
+        impl<T: Copy + DivAssign> DivAssign for $Ratio<T> {
+            #[inline]
+            fn div_assign(&mut self, other: $Ratio<T>) {
+                self.0 /= other.0;
+            }
+        }
+    }
+}
+
+impl_linear_ratio_ops!(AmplitudeRatio);
+impl_linear_ratio_ops!(PowerRatio);
+
+// Applying an amplitude ratio to a sample scales the sample.
+macro_rules! impl_amplitude_ratio_sample_ops {
+    ($T: ty) => {
+        impl Mul<$T> for AmplitudeRatio<$T> {
+            type Output = $T;
+
+            #[inline]
+            fn mul(self, sample: $T) -> $T {
+                self.0 * sample
+            }
+        }
+
+        impl Mul<AmplitudeRatio<$T>> for $T {
+            type Output = $T;
+
+            #[inline]
+            fn mul(self, amplitude: AmplitudeRatio<$T>) -> $T {
+                self * amplitude.0
+            }
+        }
+
+        impl MulAssign<AmplitudeRatio<$T>> for $T {
+            #[inline]
+            fn mul_assign(&mut self, amplitude: AmplitudeRatio<$T>) {
+                *self *= amplitude.0;
+            }
+        }
+    }
+}
+
+impl_amplitude_ratio_sample_ops!(f32);
+impl_amplitude_ratio_sample_ops!(f64);
+
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 mod test {    
     use std::{f32, f64};
     use AmplitudeRatio;
     use DecibelRatio;
+    use PowerRatio;
+
+    fn assert_close_f64(actual: f64, expected: f64) {
+        assert!(actual >= expected - 0.001 && actual <= expected + 0.001,
+                "{} is not close to {}", actual, expected);
+    }
 
     #[test]
     fn test_decibels_to_amplitude_with_different_values_f32() {
@@ -200,4 +525,110 @@ mod test {
                 assert!(result.decibel_value() >= expected_decibels - 0.001 &&
                         result.decibel_value() <= expected_decibels + 0.001);
     } 
+
+    #[test]
+    fn test_power_to_decibels_with_different_values() {
+        // A power ratio of 1 should be 0 dB.
+        test_power_to_decibels_f32(1.0, 0.0);
+        test_power_to_decibels_f64(1.0, 0.0);
+
+        // Half the power should be close to -3 dB.
+        test_power_to_decibels_f32(0.5, -3.01029995663981);
+        test_power_to_decibels_f64(0.5, -3.01029995663981);
+
+        // Ten times the power should be exactly +10 dB.
+        test_power_to_decibels_f32(10.0, 10.0);
+        test_power_to_decibels_f64(10.0, 10.0);
+
+        // 0 is a special case. We should get an infinity.
+        test_power_to_decibels_f32(0.0, f32::NEG_INFINITY);
+        test_power_to_decibels_f64(0.0, f64::NEG_INFINITY);
+    }
+
+    fn test_power_to_decibels_f32(power: f32, expected_decibels: f32) {
+        let result: DecibelRatio<_> = PowerRatio(power).into();
+        assert!(result.decibel_value() >= expected_decibels - 0.001 &&
+                result.decibel_value() <= expected_decibels + 0.001);
+
+        let round_trip: PowerRatio<_> = result.into();
+        assert!(round_trip.power_value() >= power - 0.001 &&
+                round_trip.power_value() <= power + 0.001);
+    }
+
+    fn test_power_to_decibels_f64(power: f64, expected_decibels: f64) {
+        let result: DecibelRatio<_> = PowerRatio(power).into();
+        assert!(result.decibel_value() >= expected_decibels - 0.001 &&
+                result.decibel_value() <= expected_decibels + 0.001);
+
+        let round_trip: PowerRatio<_> = result.into();
+        assert!(round_trip.power_value() >= power - 0.001 &&
+                round_trip.power_value() <= power + 0.001);
+    }
+
+    #[test]
+    fn test_power_and_amplitude_agree_on_decibels() {
+        // Doubling the amplitude quadruples the power, and both should be
+        // close to +6 dB.
+        let power: PowerRatio<f64> = AmplitudeRatio(2.0).into();
+        assert_eq!(power, PowerRatio(4.0));
+        let amplitude: AmplitudeRatio<f64> = PowerRatio(4.0).into();
+        assert_eq!(amplitude, AmplitudeRatio(2.0));
+
+        let from_power: DecibelRatio<f64> = PowerRatio(4.0).into();
+        let from_amplitude: DecibelRatio<f64> = AmplitudeRatio(2.0).into();
+        assert!((from_power.decibel_value() - from_amplitude.decibel_value()).abs() < 1e-12);
+
+        let power: PowerRatio<f32> = AmplitudeRatio(0.5).into();
+        assert_eq!(power, PowerRatio(0.25));
+    }
+
+    #[test]
+    fn test_decibel_arithmetic_matches_amplitude_arithmetic() {
+        let first = DecibelRatio(6.0);
+        let second = DecibelRatio(-10.0);
+
+        // Cascading gains adds decibels and multiplies amplitudes.
+        let cascaded: AmplitudeRatio<f64> = (first + second).into();
+        let first_amplitude: AmplitudeRatio<f64> = first.into();
+        let second_amplitude: AmplitudeRatio<f64> = second.into();
+        assert_close_f64(cascaded.amplitude_value(),
+                         (first_amplitude * second_amplitude).amplitude_value());
+
+        // Removing a gain subtracts decibels and divides amplitudes.
+        let removed: AmplitudeRatio<f64> = (first - second).into();
+        assert_close_f64(removed.amplitude_value(),
+                         (first_amplitude / second_amplitude).amplitude_value());
+
+        // Negating a gain inverts the amplitude.
+        let inverted: AmplitudeRatio<f64> = (-first).into();
+        assert_close_f64(inverted.amplitude_value(), 1.0 / first_amplitude.amplitude_value());
+
+        // Scaling a gain raises the amplitude to that power.
+        let doubled: AmplitudeRatio<f64> = (first * 2.0).into();
+        assert_close_f64(doubled.amplitude_value(), first_amplitude.amplitude_value().powi(2));
+
+        let mut total = first;
+        total += second;
+        total -= DecibelRatio(1.0);
+        assert_eq!(total, DecibelRatio(-5.0));
+
+        let mut amplitude = AmplitudeRatio(2.0);
+        amplitude *= AmplitudeRatio(3.0);
+        amplitude /= AmplitudeRatio(4.0);
+        assert_eq!(amplitude, AmplitudeRatio(1.5));
+
+        let power = PowerRatio(2.0) * PowerRatio(5.0) / PowerRatio(4.0);
+        assert_eq!(power, PowerRatio(2.5));
+    }
+
+    #[test]
+    fn test_amplitude_ratio_applies_to_samples() {
+        let gain = AmplitudeRatio(0.5f32);
+        assert_eq!(gain * 0.8f32, 0.4);
+        assert_eq!(0.8f32 * gain, 0.4);
+
+        let mut sample = 0.25f64;
+        sample *= AmplitudeRatio(4.0);
+        assert_eq!(sample, 1.0);
+    }
 }
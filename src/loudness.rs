@@ -0,0 +1,569 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loudness measurement as specified by ITU-R BS.1770-4 and EBU R128.
+//!
+//! The `LoudnessMeter` applies the K-weighting filter to each channel, sums
+//! the channels' mean squares with their BS.1770 weights, and reports:
+//!
+//! * momentary loudness, over the last 400 ms,
+//! * short-term loudness, over the last 3 s, and
+//! * integrated loudness, over everything measured so far, gated with the
+//!   absolute gate of -70 LUFS and the relative gate of -10 LU, and
+//! * loudness range (LRA), as specified by EBU Tech 3342.
+//!
+//! `LoudnessRange` computes the loudness range from short-term loudness
+//! values that come from elsewhere, and `loudness_range()` measures it in one
+//! shot on a buffer.
+//!
+//! Readings are `DecibelRatio` values in LUFS, and differences between them
+//! are in LU. Silence, or a measurement where every block is gated out, reads
+//! negative infinity.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::loudness::LoudnessMeter;
+//!
+//! fn main() {
+//!     // One second of a 1 kHz sine at -23 dBFS in both channels.
+//!     let amplitude = 10f64.powf(-23.0 / 20.0);
+//!     let mut samples = Vec::new();
+//!     for i in 0..48000 {
+//!         let sample = amplitude * (i as f64 * 2.0 * std::f64::consts::PI / 48.0).sin();
+//!         samples.push(sample);
+//!         samples.push(sample);
+//!     }
+//!
+//!     let mut meter = LoudnessMeter::new(48000.0, 2);
+//!     meter.process(&samples);
+//!     let integrated = meter.integrated().decibel_value();
+//!     assert!(integrated > -23.1 && integrated < -22.9);
+//! }
+//! ```
+
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+use biquad::Biquad;
+use pcm::{FullScale, Sample};
+use {DecibelRatio, PowerRatio};
+
+/// The absolute gate for integrated loudness, in LUFS.
+pub const ABSOLUTE_GATE: f64 = -70.0;
+
+/// The relative gate for integrated loudness, in LU below the loudness of the
+/// blocks that pass the absolute gate.
+pub const RELATIVE_GATE: f64 = -10.0;
+
+/// The relative gate for loudness range, in LU below the loudness of the
+/// short-term values that pass the absolute gate.
+pub const LOUDNESS_RANGE_RELATIVE_GATE: f64 = -20.0;
+
+// The offset in BS.1770's loudness formula, which makes a 1 kHz sine at
+// 0 dBFS in one channel read -3.01 LUFS.
+const LOUDNESS_OFFSET: f64 = -0.691;
+
+// Loudness is accumulated in sub-blocks of 100 ms, which make up the 400 ms
+// momentary window and gating blocks and the 3 s short-term window.
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+/// Converts a weighted mean square into loudness in LUFS.
+#[inline]
+pub(crate) fn loudness_of(mean_square: f64) -> DecibelRatio<f64> {
+    DecibelRatio::from(PowerRatio(mean_square)) + DecibelRatio(LOUDNESS_OFFSET)
+}
+
+/// Returns the gated loudness of a set of 400 ms blocks, given as weighted
+/// mean squares, with the absolute gate and the given relative gate.
+pub(crate) fn gated_loudness<'a, I>(blocks: I, relative_gate: f64) -> DecibelRatio<f64>
+    where I: Iterator<Item = &'a f64> + Clone
+{
+    let absolute_threshold = PowerRatio::from(DecibelRatio(ABSOLUTE_GATE - LOUDNESS_OFFSET)).power_value();
+    let mean_above = |threshold: f64| {
+        let (sum, count) = blocks.clone()
+            .filter(|&&block| block > threshold)
+            .fold((0.0, 0usize), |(sum, count), &block| (sum + block, count + 1));
+        if count == 0 { 0.0 } else { sum / count as f64 }
+    };
+
+    let absolute_mean = mean_above(absolute_threshold);
+    if absolute_mean == 0.0 {
+        return DecibelRatio(f64::NEG_INFINITY);
+    }
+    let relative_threshold = absolute_mean * PowerRatio::from(DecibelRatio(relative_gate)).power_value();
+    loudness_of(mean_above(relative_threshold.max(absolute_threshold)))
+}
+
+/// The K-weighting filter from BS.1770: a high-shelf that models the acoustic
+/// effect of the head, followed by a high-pass filter.
+///
+/// The coefficients are derived from the analog prototypes, so they match the
+/// 48 kHz coefficients in the specification and work at any sample rate.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    pub fn new(sample_rate: f64) -> KWeighting {
+        let f0 = 1_681.974_450_955_533;
+        let gain = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let shelf = Biquad::new(vh + vb * k / q + k * k,
+                                2.0 * (k * k - vh),
+                                vh - vb * k / q + k * k,
+                                1.0 + k / q + k * k,
+                                2.0 * (k * k - 1.0),
+                                1.0 - k / q + k * k);
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (PI * f0 / sample_rate).tan();
+        // Only the feedback coefficients are normalized in the specification,
+        // which gives the high-pass filter a passband gain slightly above 1.
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(a0, -2.0 * a0, a0,
+                                    a0,
+                                    2.0 * (k * k - 1.0),
+                                    1.0 - k / q + k * k);
+
+        KWeighting { shelf, high_pass }
+    }
+
+    #[inline]
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(input))
+    }
+
+    pub fn reset(&mut self) {
+        self.shelf.reset();
+        self.high_pass.reset();
+    }
+}
+
+/// Returns the BS.1770 channel weights for a channel count, assuming the
+/// common layouts: L, R, C, Ls, Rs for five channels and L, R, C, LFE, Ls, Rs
+/// for six. Other channel counts weight every channel equally.
+fn default_channel_weights(channels: usize) -> Vec<f64> {
+    match channels {
+        5 => vec![1.0, 1.0, 1.0, 1.41, 1.41],
+        6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        _ => vec![1.0; channels],
+    }
+}
+
+/// A loudness meter following ITU-R BS.1770-4 and EBU R128.
+#[derive(Clone, Debug)]
+pub struct LoudnessMeter {
+    full_scale: FullScale,
+    filters: Vec<KWeighting>,
+    weights: Vec<f64>,
+    sub_block_length: usize,
+    // The number of frames in the current sub-block, and the weighted sum of
+    // their squares.
+    sub_block_frames: usize,
+    sub_block_sum: f64,
+    // The weighted sums of squares of the most recent sub-blocks, as a ring.
+    sub_blocks: [f64; SHORT_TERM_SUB_BLOCKS],
+    sub_block_position: usize,
+    sub_blocks_seen: usize,
+    // The weighted mean square of every 400 ms gating block so far.
+    gating_blocks: Vec<f64>,
+    range: LoudnessRange,
+}
+
+impl LoudnessMeter {
+    /// Creates a loudness meter for interleaved blocks with the given sample
+    /// rate and number of channels.
+    ///
+    /// Five- and six-channel meters weight the surround channels and skip
+    /// the LFE channel, as described in `set_channel_weight()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero, or if the sample rate is below 10 Hz.
+    pub fn new(sample_rate: f64, channels: usize) -> LoudnessMeter {
+        assert!(channels > 0, "a meter needs at least one channel");
+        let sub_block_length = (sample_rate / 10.0).round() as usize;
+        assert!(sub_block_length > 0, "the sample rate must be at least 10 Hz");
+
+        LoudnessMeter {
+            full_scale: FullScale::default(),
+            filters: vec![KWeighting::new(sample_rate); channels],
+            weights: default_channel_weights(channels),
+            sub_block_length,
+            sub_block_frames: 0,
+            sub_block_sum: 0.0,
+            sub_blocks: [0.0; SHORT_TERM_SUB_BLOCKS],
+            sub_block_position: 0,
+            sub_blocks_seen: 0,
+            gating_blocks: Vec::new(),
+            range: LoudnessRange::new(),
+        }
+    }
+
+    /// Sets the full-scale convention used to normalize integer samples.
+    pub fn set_full_scale(&mut self, full_scale: FullScale) {
+        self.full_scale = full_scale;
+    }
+
+    /// Sets the weight of a channel.
+    ///
+    /// BS.1770 weights the left, right and centre channels by 1.0 and the
+    /// surround channels by 1.41, and leaves out the LFE channel, which can be
+    /// done with a weight of 0.0. Five- and six-channel meters start with
+    /// those weights for the L, R, C, Ls, Rs and L, R, C, LFE, Ls, Rs layouts;
+    /// other meters start with every channel weighted by 1.0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn set_channel_weight(&mut self, channel: usize, weight: f64) {
+        self.weights[channel] = weight;
+    }
+
+    /// Returns the number of channels.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Measures a block of interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't contain a whole number of frames.
+    pub fn process<S: Sample>(&mut self, interleaved: &[S]) {
+        let channels = self.channels();
+        assert_eq!(interleaved.len() % channels, 0,
+                   "the block must contain a whole number of frames");
+
+        for frame in interleaved.chunks(channels) {
+            let mut sum = 0.0;
+            for ((filter, &weight), &sample) in self.filters.iter_mut().zip(self.weights.iter()).zip(frame) {
+                let filtered = filter.process(sample.to_normalized(self.full_scale));
+                sum += weight * filtered * filtered;
+            }
+            self.sub_block_sum += sum;
+            self.sub_block_frames += 1;
+
+            if self.sub_block_frames == self.sub_block_length {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        self.sub_blocks[self.sub_block_position] = self.sub_block_sum;
+        self.sub_block_position = (self.sub_block_position + 1) % SHORT_TERM_SUB_BLOCKS;
+        self.sub_blocks_seen += 1;
+        self.sub_block_sum = 0.0;
+        self.sub_block_frames = 0;
+
+        // Gating blocks are 400 ms long and overlap by 75%, so a new one
+        // completes with every sub-block once the first has filled up.
+        if self.sub_blocks_seen >= MOMENTARY_SUB_BLOCKS {
+            let mean_square = self.recent_mean_square(MOMENTARY_SUB_BLOCKS);
+            self.gating_blocks.push(mean_square);
+        }
+
+        // Short-term values for the loudness range are taken every 100 ms,
+        // once the first 3 s window has filled up.
+        if self.sub_blocks_seen >= SHORT_TERM_SUB_BLOCKS {
+            let short_term = self.short_term();
+            self.range.push(short_term);
+        }
+    }
+
+    // The weighted mean square of the most recent sub-blocks. Sub-blocks
+    // from before the meter started count as silence.
+    fn recent_mean_square(&self, sub_blocks: usize) -> f64 {
+        let sum: f64 = (1..=sub_blocks)
+            .map(|age| (self.sub_block_position + SHORT_TERM_SUB_BLOCKS - age) % SHORT_TERM_SUB_BLOCKS)
+            .map(|index| self.sub_blocks[index])
+            .sum();
+        sum / (sub_blocks * self.sub_block_length) as f64
+    }
+
+    /// Returns the momentary loudness, over the last 400 ms, in LUFS.
+    pub fn momentary(&self) -> DecibelRatio<f64> {
+        loudness_of(self.recent_mean_square(MOMENTARY_SUB_BLOCKS))
+    }
+
+    /// Returns the short-term loudness, over the last 3 s, in LUFS.
+    pub fn short_term(&self) -> DecibelRatio<f64> {
+        loudness_of(self.recent_mean_square(SHORT_TERM_SUB_BLOCKS))
+    }
+
+    /// Returns the integrated loudness of everything measured since the
+    /// meter was created or reset, in LUFS.
+    pub fn integrated(&self) -> DecibelRatio<f64> {
+        gated_loudness(self.gating_blocks.iter(), RELATIVE_GATE)
+    }
+
+    /// Returns the weighted mean square of every 400 ms gating block so far,
+    /// for gating over several measurements together.
+    pub(crate) fn gating_blocks(&self) -> &[f64] {
+        &self.gating_blocks
+    }
+
+    /// Returns the loudness range of everything measured since the meter was
+    /// created or reset, in LU.
+    pub fn loudness_range(&self) -> DecibelRatio<f64> {
+        self.range.loudness_range()
+    }
+
+    /// Forgets all measured samples.
+    pub fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+        self.sub_block_frames = 0;
+        self.sub_block_sum = 0.0;
+        self.sub_blocks = [0.0; SHORT_TERM_SUB_BLOCKS];
+        self.sub_block_position = 0;
+        self.sub_blocks_seen = 0;
+        self.gating_blocks.clear();
+        self.range.reset();
+    }
+}
+
+/// Computes the loudness range (LRA) of a programme from its short-term
+/// loudness, as specified by EBU Tech 3342.
+///
+/// Push short-term loudness values, measured over 3 s windows at least every
+/// 100 ms, as they arrive. The loudness range gates them with the absolute
+/// gate of -70 LUFS and the relative gate of -20 LU, and returns the spread
+/// between the 10th and 95th percentiles of the rest.
+///
+/// `LoudnessMeter` does this by itself; use this directly for short-term
+/// values that come from somewhere else.
+#[derive(Clone, Debug, Default)]
+pub struct LoudnessRange {
+    short_term: Vec<f64>,
+}
+
+impl LoudnessRange {
+    /// Creates an empty loudness range calculator.
+    pub fn new() -> LoudnessRange {
+        LoudnessRange { short_term: Vec::new() }
+    }
+
+    /// Adds a short-term loudness value, in LUFS.
+    pub fn push(&mut self, short_term: DecibelRatio<f64>) {
+        self.short_term.push(short_term.decibel_value());
+    }
+
+    /// Returns the loudness range of the values so far, in LU. This is zero
+    /// if no values pass the gates.
+    pub fn loudness_range(&self) -> DecibelRatio<f64> {
+        let mut gated: Vec<f64> = self.short_term.iter()
+            .cloned()
+            .filter(|&loudness| loudness > ABSOLUTE_GATE)
+            .collect();
+        if gated.is_empty() {
+            return DecibelRatio(0.0);
+        }
+
+        let mean_square = gated.iter()
+            .map(|&loudness| PowerRatio::from(DecibelRatio(loudness)).power_value())
+            .sum::<f64>() / gated.len() as f64;
+        let relative_threshold = DecibelRatio::from(PowerRatio(mean_square)).decibel_value()
+            + LOUDNESS_RANGE_RELATIVE_GATE;
+        gated.retain(|&loudness| loudness > relative_threshold);
+        gated.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let percentile = |p: f64| gated[((gated.len() - 1) as f64 * p).round() as usize];
+        DecibelRatio(percentile(0.95) - percentile(0.10))
+    }
+
+    /// Forgets all values.
+    pub fn reset(&mut self) {
+        self.short_term.clear();
+    }
+}
+
+/// Measures the loudness range of a buffer of interleaved samples in one
+/// shot, in LU.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `LoudnessMeter::new()` and
+/// `LoudnessMeter::process()`.
+pub fn loudness_range<S: Sample>(interleaved: &[S], sample_rate: f64, channels: usize) -> DecibelRatio<f64> {
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.process(interleaved);
+    meter.loudness_range()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(actual: DecibelRatio<f64>, expected: f64, tolerance: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= tolerance,
+                "{:?} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    // Generates a 1 kHz sine with the given peak level in dBFS in every
+    // channel, as used by the EBU Tech 3341 test signals.
+    fn sine(sample_rate: f64, channels: &[f64], seconds: f64) -> Vec<f64> {
+        let frames = (sample_rate * seconds).round() as usize;
+        let amplitudes: Vec<f64> = channels.iter().map(|&dbfs| 10f64.powf(dbfs / 20.0)).collect();
+        let mut samples = Vec::with_capacity(frames * channels.len());
+        for i in 0..frames {
+            let phase = (i as f64 * 2.0 * PI * 1000.0 / sample_rate).sin();
+            samples.extend(amplitudes.iter().map(|&amplitude| amplitude * phase));
+        }
+        samples
+    }
+
+    fn measure(sample_rate: f64, segments: &[(f64, f64)]) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(sample_rate, 2);
+        for &(dbfs, seconds) in segments {
+            meter.process(&sine(sample_rate, &[dbfs, dbfs], seconds));
+        }
+        meter
+    }
+
+    #[test]
+    fn test_k_weighting_matches_specification_at_48_khz() {
+        let filter = KWeighting::new(48000.0);
+        let shelf = Biquad::new(1.535_124_859_586_97, -2.691_696_189_406_38, 1.198_392_810_852_85,
+                                1.0, -1.690_659_293_182_41, 0.732_480_774_215_85);
+        let high_pass = Biquad::new(1.0, -2.0, 1.0, 1.0, -1.990_047_454_833_98, 0.990_072_250_366_21);
+        for (actual, expected) in [filter.shelf, filter.high_pass].iter().zip([shelf, high_pass].iter()) {
+            for (actual, expected) in actual.coefficients().iter().zip(expected.coefficients().iter()) {
+                assert!((actual - expected).abs() < 1e-8, "{} should be {}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tech_3341_case_1_and_2_constant_sines() {
+        // A stereo 1 kHz sine at -23 dBFS reads -23 LUFS everywhere.
+        let meter = measure(48000.0, &[(-23.0, 20.0)]);
+        assert_close(meter.momentary(), -23.0, 0.1);
+        assert_close(meter.short_term(), -23.0, 0.1);
+        assert_close(meter.integrated(), -23.0, 0.1);
+
+        let meter = measure(48000.0, &[(-33.0, 20.0)]);
+        assert_close(meter.momentary(), -33.0, 0.1);
+        assert_close(meter.short_term(), -33.0, 0.1);
+        assert_close(meter.integrated(), -33.0, 0.1);
+    }
+
+    #[test]
+    fn test_tech_3341_case_3_relative_gate() {
+        let meter = measure(48000.0, &[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)]);
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_tech_3341_case_4_absolute_gate() {
+        let meter = measure(48000.0, &[(-72.0, 10.0), (-36.0, 10.0), (-23.0, 60.0),
+                                       (-36.0, 10.0), (-72.0, 10.0)]);
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_tech_3341_case_6_five_channels() {
+        // L and R at -28 dBFS, C at -24 dBFS and Ls and Rs at -30 dBFS.
+        let mut meter = LoudnessMeter::new(48000.0, 5);
+        meter.process(&sine(48000.0, &[-28.0, -28.0, -24.0, -30.0, -30.0], 20.0));
+        assert_close(meter.integrated(), -23.0, 0.1);
+    }
+
+    #[test]
+    fn test_other_sample_rates_and_formats() {
+        let meter = measure(44100.0, &[(-23.0, 10.0)]);
+        assert_close(meter.integrated(), -23.0, 0.1);
+
+        let mut meter = LoudnessMeter::new(96000.0, 1);
+        let samples: Vec<i16> = sine(96000.0, &[-20.0], 5.0).iter()
+            .map(|&sample| i16::from_normalized(sample, FullScale::Positive))
+            .collect();
+        meter.process(&samples);
+        // A single channel reads 3.01 LU lower than the same sine in stereo.
+        assert_close(meter.integrated(), -23.01, 0.1);
+    }
+
+    #[test]
+    fn test_silence_is_gated_out() {
+        let mut meter = LoudnessMeter::new(48000.0, 2);
+        assert_eq!(meter.integrated(), DecibelRatio(f64::NEG_INFINITY));
+        meter.process(&vec![0.0f32; 48000 * 2]);
+        assert_eq!(meter.momentary(), DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(meter.integrated(), DecibelRatio(f64::NEG_INFINITY));
+
+        meter.process(&sine(48000.0, &[-23.0, -23.0], 2.0));
+        meter.reset();
+        assert_eq!(meter.integrated(), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    fn stereo_sequence(sample_rate: f64, segments: &[f64]) -> Vec<f64> {
+        let mut samples = Vec::new();
+        for &dbfs in segments {
+            samples.extend(sine(sample_rate, &[dbfs, dbfs], 20.0));
+        }
+        samples
+    }
+
+    #[test]
+    fn test_tech_3342_cases_1_to_3_in_one_shot() {
+        let samples = stereo_sequence(48000.0, &[-20.0, -30.0]);
+        assert_close(loudness_range(&samples, 48000.0, 2), 10.0, 1.0);
+
+        let samples = stereo_sequence(48000.0, &[-20.0, -15.0]);
+        assert_close(loudness_range(&samples, 48000.0, 2), 5.0, 1.0);
+
+        let samples = stereo_sequence(48000.0, &[-40.0, -20.0]);
+        assert_close(loudness_range(&samples, 48000.0, 2), 20.0, 1.0);
+    }
+
+    #[test]
+    fn test_tech_3342_case_4_on_a_stream() {
+        let samples = stereo_sequence(48000.0, &[-50.0, -35.0, -20.0, -35.0, -50.0]);
+        let mut meter = LoudnessMeter::new(48000.0, 2);
+        for block in samples.chunks(2 * 4800) {
+            meter.process(block);
+        }
+        assert_close(meter.loudness_range(), 15.0, 1.0);
+
+        meter.reset();
+        assert_eq!(meter.loudness_range(), DecibelRatio(0.0));
+    }
+
+    #[test]
+    fn test_loudness_range_from_short_term_values() {
+        let mut range = LoudnessRange::new();
+        assert_eq!(range.loudness_range(), DecibelRatio(0.0));
+
+        // Values below the absolute gate, and more than 20 LU below the
+        // rest, don't count.
+        for &loudness in [-80.0, -75.0, -50.0].iter() {
+            range.push(DecibelRatio(loudness));
+        }
+        for i in 0..=100 {
+            range.push(DecibelRatio(-30.0 + i as f64 * 0.1));
+        }
+        assert_close(range.loudness_range(), 8.5, 0.001);
+    }
+}
@@ -0,0 +1,100 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The math backend behind the conversions.
+//!
+//! The functions follow libm's naming, with an `f` suffix for the `f32`
+//! variants. When the `libm` feature is enabled they come from libm, even if
+//! `std` is enabled too, so that both backends can be tested on the desktop.
+//! Otherwise they forward to the inherent methods from std.
+
+#[cfg(feature = "libm")]
+pub use libm::{ceil, exp, log10, log10f, pow, powf, round, sqrt, sqrtf};
+
+#[cfg(not(feature = "libm"))]
+pub use self::std_backend::*;
+
+#[cfg(not(feature = "libm"))]
+mod std_backend {
+    #[inline]
+    pub fn ceil(x: f64) -> f64 {
+        x.ceil()
+    }
+
+    #[inline]
+    pub fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+
+    #[inline]
+    pub fn log10(x: f64) -> f64 {
+        x.log10()
+    }
+
+    #[inline]
+    pub fn log10f(x: f32) -> f32 {
+        x.log10()
+    }
+
+    #[inline]
+    pub fn pow(x: f64, y: f64) -> f64 {
+        x.powf(y)
+    }
+
+    #[inline]
+    pub fn powf(x: f32, y: f32) -> f32 {
+        x.powf(y)
+    }
+
+    #[inline]
+    pub fn round(x: f64) -> f64 {
+        x.round()
+    }
+
+    #[inline]
+    pub fn sqrt(x: f64) -> f64 {
+        x.sqrt()
+    }
+
+    #[inline]
+    pub fn sqrtf(x: f32) -> f32 {
+        x.sqrt()
+    }
+}
+
+#[cfg(all(test, feature = "std", feature = "libm"))]
+mod test {
+    use super::*;
+
+    fn assert_agree(libm: f64, std: f64, tolerance: f64) {
+        assert!((libm - std).abs() <= tolerance * std.abs().max(1.0),
+                "libm gave {} but std gave {}", libm, std);
+    }
+
+    #[test]
+    fn test_libm_agrees_with_std() {
+        let values = [1e-9, 1.0 / 32767.0, 0.001, 0.5, 1.0, 2.0, 3.1622776601683795, 140.0];
+        for &x in values.iter() {
+            assert_agree(log10(x), x.log10(), 1e-14);
+            assert_agree(pow(10.0, x / 20.0), 10f64.powf(x / 20.0), 1e-14);
+            assert_agree(sqrt(x), x.sqrt(), 1e-14);
+            assert_eq!(round(x * 1000.0), (x * 1000.0).round());
+            assert_eq!(ceil(x * 1000.0), (x * 1000.0).ceil());
+            assert_agree(exp(-1.0 / x), (-1.0 / x).exp(), 1e-14);
+
+            let x = x as f32;
+            assert_agree(log10f(x) as f64, x.log10() as f64, 1e-6);
+            assert_agree(powf(10.0, x / 20.0) as f64, 10f32.powf(x / 20.0) as f64, 1e-6);
+            assert_agree(sqrtf(x) as f64, x.sqrt() as f64, 1e-6);
+        }
+    }
+}
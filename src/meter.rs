@@ -0,0 +1,713 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Level meters that report readings in dBFS.
+//!
+//! * `PeakMeter` tracks sample peaks.
+//! * `TruePeakMeter` tracks true peaks, including inter-sample peaks, in
+//!   dBTP.
+//! * `RmsMeter` measures RMS levels over a sliding window.
+//!
+//! The meters accept blocks of interleaved samples in any format that
+//! implements `pcm::Sample`, so the same meter works for `f32`, `f64` and
+//! integer buffers. Silence is reported as negative infinity, just like
+//! converting an `AmplitudeRatio` of zero.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::meter::PeakMeter;
+//!
+//! fn main() {
+//!     let mut meter = PeakMeter::new(2);
+//!     meter.process(&[0.5f32, 0.0, -0.25, 0.0]);
+//!
+//!     let left = meter.peak(0).decibel_value();
+//!     assert!(left > -6.03 && left < -6.01);
+//!     assert_eq!(meter.peak(1).decibel_value(), std::f64::NEG_INFINITY);
+//! }
+//! ```
+
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+use pcm::{FullScale, Sample};
+use {AmplitudeRatio, DecibelRatio, PowerRatio};
+
+// 10·log10(2): the difference between the RMS level of a sine wave and its
+// peak level.
+const SINE_CREST_FACTOR_DECIBELS: f64 = 3.010_299_956_639_812;
+
+/// A sample-peak meter that tracks the largest sample magnitude of each
+/// channel.
+///
+/// The meter remembers the peak of the most recent block and the largest peak
+/// seen since it was created or reset.
+#[derive(Clone, Debug)]
+pub struct PeakMeter {
+    full_scale: FullScale,
+    block_peaks: Vec<f64>,
+    max_peaks: Vec<f64>,
+}
+
+impl PeakMeter {
+    /// Creates a peak meter for interleaved blocks with the given number of
+    /// channels, using the default full-scale convention for integer samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero.
+    pub fn new(channels: usize) -> PeakMeter {
+        PeakMeter::with_full_scale(channels, FullScale::default())
+    }
+
+    /// Creates a peak meter that normalizes integer samples with the given
+    /// full-scale convention.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero.
+    pub fn with_full_scale(channels: usize, full_scale: FullScale) -> PeakMeter {
+        assert!(channels > 0, "a meter needs at least one channel");
+        PeakMeter {
+            full_scale,
+            block_peaks: vec![0.0; channels],
+            max_peaks: vec![0.0; channels],
+        }
+    }
+
+    /// Returns the number of channels.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.block_peaks.len()
+    }
+
+    /// Measures a block of interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't contain a whole number of frames.
+    pub fn process<S: Sample>(&mut self, interleaved: &[S]) {
+        let channels = self.channels();
+        assert_eq!(interleaved.len() % channels, 0,
+                   "the block must contain a whole number of frames");
+
+        for peak in self.block_peaks.iter_mut() {
+            *peak = 0.0;
+        }
+        for frame in interleaved.chunks(channels) {
+            for (peak, &sample) in self.block_peaks.iter_mut().zip(frame) {
+                let magnitude = sample.to_normalized(self.full_scale).abs();
+                if magnitude > *peak {
+                    *peak = magnitude;
+                }
+            }
+        }
+        for (max, &peak) in self.max_peaks.iter_mut().zip(self.block_peaks.iter()) {
+            if peak > *max {
+                *max = peak;
+            }
+        }
+    }
+
+    /// Returns the peak of a channel in the most recent block, in dBFS.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    pub fn peak(&self, channel: usize) -> DecibelRatio<f64> {
+        AmplitudeRatio(self.block_peaks[channel]).into()
+    }
+
+    /// Returns the largest peak of a channel since the meter was created or
+    /// reset, in dBFS.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    pub fn max_peak(&self, channel: usize) -> DecibelRatio<f64> {
+        AmplitudeRatio(self.max_peaks[channel]).into()
+    }
+
+    /// Returns the largest peak across all channels since the meter was
+    /// created or reset, in dBFS.
+    pub fn overall_max_peak(&self) -> DecibelRatio<f64> {
+        let max = self.max_peaks.iter().fold(0.0, |max: f64, &peak| max.max(peak));
+        AmplitudeRatio(max).into()
+    }
+
+    /// Forgets all peaks, as if no samples had been measured.
+    pub fn reset(&mut self) {
+        for peak in self.block_peaks.iter_mut().chain(self.max_peaks.iter_mut()) {
+            *peak = 0.0;
+        }
+    }
+}
+
+// The 48-tap interpolating filter from ITU-R BS.1770-4 Annex 2, split into
+// its four phases of 12 taps each.
+const ANNEX_2_PHASES: [[f64; TRUE_PEAK_TAPS_PER_PHASE]; 4] = [
+    [0.001_708_984_375_0, 0.010_986_328_125_0, -0.019_653_320_312_5, 0.033_203_125_000_0,
+     -0.059_448_242_187_5, 0.137_329_101_562_5, 0.972_167_968_750_0, -0.102_294_921_875_0,
+     0.047_607_421_875_0, -0.026_611_328_125_0, 0.014_892_578_125_0, -0.008_300_781_250_0],
+    [-0.029_174_804_687_5, 0.029_296_875_000_0, -0.051_757_812_500_0, 0.089_111_328_125_0,
+     -0.166_503_906_250_0, 0.465_087_890_625_0, 0.779_785_156_250_0, -0.200_317_382_812_5,
+     0.101_562_500_000_0, -0.058_227_539_062_5, 0.033_081_054_687_5, -0.018_920_898_437_5],
+    [-0.018_920_898_437_5, 0.033_081_054_687_5, -0.058_227_539_062_5, 0.101_562_500_000_0,
+     -0.200_317_382_812_5, 0.779_785_156_250_0, 0.465_087_890_625_0, -0.166_503_906_250_0,
+     0.089_111_328_125_0, -0.051_757_812_500_0, 0.029_296_875_000_0, -0.029_174_804_687_5],
+    [-0.008_300_781_250_0, 0.014_892_578_125_0, -0.026_611_328_125_0, 0.047_607_421_875_0,
+     -0.102_294_921_875_0, 0.972_167_968_750_0, 0.137_329_101_562_5, -0.059_448_242_187_5,
+     0.033_203_125_000_0, -0.019_653_320_312_5, 0.010_986_328_125_0, 0.001_708_984_375_0],
+];
+
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+// Oversampling brings the sample rate to at least this, which is 4x for
+// 44.1 kHz and above, as in BS.1770.
+const TRUE_PEAK_MIN_OVERSAMPLED_RATE: f64 = 176_400.0;
+
+/// Returns the oversampling factor for a sample rate: 4x at 44.1 kHz and
+/// above, and higher powers of two below that.
+fn oversampling_factor(sample_rate: f64) -> usize {
+    let mut factor = 4;
+    while sample_rate * (factor as f64) < TRUE_PEAK_MIN_OVERSAMPLED_RATE {
+        factor *= 2;
+    }
+    factor
+}
+
+/// Designs the phases of an interpolating filter for the given factor.
+///
+/// Factors of 4 use the filter from BS.1770 Annex 2. Higher factors use a
+/// Blackman-windowed sinc with the same number of taps per phase, with each
+/// phase normalized to unity gain.
+fn interpolation_phases(factor: usize) -> Vec<[f64; TRUE_PEAK_TAPS_PER_PHASE]> {
+    if factor == 4 {
+        return ANNEX_2_PHASES.to_vec();
+    }
+
+    let length = TRUE_PEAK_TAPS_PER_PHASE * factor;
+    let center = (length - 1) as f64 / 2.0;
+    let tap = |i: usize| {
+        let t = (i as f64 - center) / factor as f64;
+        let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+        let x = 2.0 * PI * i as f64 / (length - 1) as f64;
+        sinc * (0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos())
+    };
+    (0..factor).map(|phase| {
+        let mut taps = [0.0; TRUE_PEAK_TAPS_PER_PHASE];
+        for (k, value) in taps.iter_mut().enumerate() {
+            *value = tap(phase + k * factor);
+        }
+        let sum: f64 = taps.iter().sum();
+        for value in taps.iter_mut() {
+            *value /= sum;
+        }
+        taps
+    }).collect()
+}
+
+/// A true-peak meter following ITU-R BS.1770-4 Annex 2, which reports peaks
+/// in dBTP.
+///
+/// Sample peaks miss the peaks of the reconstructed waveform between
+/// samples. This meter oversamples each channel, by 4x at 44.1 kHz and above
+/// and by more at lower sample rates, and tracks the largest magnitude of the
+/// oversampled signal. The samples themselves are included too, so a true
+/// peak never reads lower than the sample peak.
+///
+/// Like `PeakMeter`, it remembers the peak of the most recent block and the
+/// largest peak seen since it was created or reset, along with the frame
+/// where that largest peak occurred.
+#[derive(Clone, Debug)]
+pub struct TruePeakMeter {
+    full_scale: FullScale,
+    channels: usize,
+    phases: Vec<[f64; TRUE_PEAK_TAPS_PER_PHASE]>,
+    // The most recent samples of each channel, newest first, stored twice in
+    // a row so that a whole filter window is always contiguous.
+    history: Vec<f64>,
+    position: usize,
+    // The number of frames measured so far.
+    frames: u64,
+    block_peaks: Vec<f64>,
+    max_peaks: Vec<f64>,
+    max_positions: Vec<Option<u64>>,
+}
+
+impl TruePeakMeter {
+    /// Creates a true-peak meter for interleaved blocks with the given sample
+    /// rate and number of channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero, or if the sample rate isn't positive.
+    pub fn new(sample_rate: f64, channels: usize) -> TruePeakMeter {
+        assert!(channels > 0, "a meter needs at least one channel");
+        assert!(sample_rate > 0.0, "the sample rate must be positive");
+        TruePeakMeter {
+            full_scale: FullScale::default(),
+            channels,
+            phases: interpolation_phases(oversampling_factor(sample_rate)),
+            history: vec![0.0; 2 * TRUE_PEAK_TAPS_PER_PHASE * channels],
+            position: 0,
+            frames: 0,
+            block_peaks: vec![0.0; channels],
+            max_peaks: vec![0.0; channels],
+            max_positions: vec![None; channels],
+        }
+    }
+
+    /// Sets the full-scale convention used to normalize integer samples.
+    pub fn set_full_scale(&mut self, full_scale: FullScale) {
+        self.full_scale = full_scale;
+    }
+
+    /// Returns the number of channels.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns the oversampling factor.
+    #[inline]
+    pub fn oversampling_factor(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Measures a block of interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't contain a whole number of frames.
+    pub fn process<S: Sample>(&mut self, interleaved: &[S]) {
+        assert_eq!(interleaved.len() % self.channels, 0,
+                   "the block must contain a whole number of frames");
+
+        // The interpolated output lags the input by half the filter length,
+        // so the frame of an interpolated peak is found by going back that far.
+        let factor = self.phases.len() as f64;
+        let delay = (TRUE_PEAK_TAPS_PER_PHASE as f64 * factor - 1.0) / (2.0 * factor);
+
+        for peak in self.block_peaks.iter_mut() {
+            *peak = 0.0;
+        }
+        for frame in interleaved.chunks(self.channels) {
+            self.position = (self.position + TRUE_PEAK_TAPS_PER_PHASE - 1) % TRUE_PEAK_TAPS_PER_PHASE;
+            for (channel, &sample) in frame.iter().enumerate() {
+                let sample = sample.to_normalized(self.full_scale);
+                let start = channel * 2 * TRUE_PEAK_TAPS_PER_PHASE;
+                let history = &mut self.history[start..start + 2 * TRUE_PEAK_TAPS_PER_PHASE];
+                history[self.position] = sample;
+                history[self.position + TRUE_PEAK_TAPS_PER_PHASE] = sample;
+                let window = &history[self.position..self.position + TRUE_PEAK_TAPS_PER_PHASE];
+
+                let mut peak = sample.abs();
+                let mut peak_frame = self.frames as f64;
+                for (phase, taps) in self.phases.iter().enumerate() {
+                    let interpolated: f64 = taps.iter().zip(window).map(|(tap, x)| tap * x).sum();
+                    if interpolated.abs() > peak {
+                        peak = interpolated.abs();
+                        peak_frame = self.frames as f64 + phase as f64 / factor - delay;
+                    }
+                }
+
+                if peak > self.block_peaks[channel] {
+                    self.block_peaks[channel] = peak;
+                }
+                if peak > self.max_peaks[channel] {
+                    self.max_peaks[channel] = peak;
+                    self.max_positions[channel] = Some(peak_frame.round().max(0.0) as u64);
+                }
+            }
+            self.frames += 1;
+        }
+    }
+
+    /// Returns the true peak of a channel in the most recent block, in dBTP.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    pub fn peak(&self, channel: usize) -> DecibelRatio<f64> {
+        AmplitudeRatio(self.block_peaks[channel]).into()
+    }
+
+    /// Returns the largest true peak of a channel since the meter was created
+    /// or reset, in dBTP.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    pub fn max_peak(&self, channel: usize) -> DecibelRatio<f64> {
+        AmplitudeRatio(self.max_peaks[channel]).into()
+    }
+
+    /// Returns the largest true peak across all channels since the meter was
+    /// created or reset, in dBTP.
+    pub fn overall_max_peak(&self) -> DecibelRatio<f64> {
+        let max = self.max_peaks.iter().fold(0.0, |max: f64, &peak| max.max(peak));
+        AmplitudeRatio(max).into()
+    }
+
+    /// Returns the index of the frame nearest to the largest true peak of a
+    /// channel, counting from the first frame measured since the meter was
+    /// created or reset. Returns `None` if the channel has only been silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    pub fn max_peak_position(&self, channel: usize) -> Option<u64> {
+        self.max_positions[channel]
+    }
+
+    /// Forgets all peaks and samples, as if no samples had been measured.
+    pub fn reset(&mut self) {
+        for value in self.history.iter_mut()
+            .chain(self.block_peaks.iter_mut())
+            .chain(self.max_peaks.iter_mut()) {
+            *value = 0.0;
+        }
+        for position in self.max_positions.iter_mut() {
+            *position = None;
+        }
+        self.position = 0;
+        self.frames = 0;
+    }
+}
+
+/// How an `RmsMeter` integrates the squared samples.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Integration {
+    /// The mean square over a sliding window of the given length, with every
+    /// sample in the window weighted equally.
+    Rectangular,
+    /// An exponentially weighted mean square, with the window length as the
+    /// time constant.
+    Exponential,
+}
+
+/// A streaming RMS meter with a configurable integration window.
+///
+/// The meter converts the mean square of each channel into decibels with the
+/// power law, 10·log10, so a full-scale square wave reads 0 dBFS and a
+/// full-scale sine wave reads -3.01 dBFS. Call `set_sine_referenced(true)`
+/// to follow the AES17 convention instead, where a full-scale sine wave reads
+/// 0 dBFS RMS.
+#[derive(Clone, Debug)]
+pub struct RmsMeter {
+    full_scale: FullScale,
+    integration: Integration,
+    sine_referenced: bool,
+    channels: usize,
+    // The running mean square of each channel, for exponential integration,
+    // or the running sum of squares, for rectangular integration.
+    states: Vec<f64>,
+    // The squared samples in the window, for rectangular integration. The
+    // frames are interleaved, just like the input.
+    history: Vec<f64>,
+    window_length: usize,
+    position: usize,
+    coefficient: f64,
+}
+
+impl RmsMeter {
+    /// Creates an RMS meter for interleaved blocks with the given number of
+    /// channels, integrating over a window of `window_seconds`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is zero, or if the window is shorter than one
+    /// sample.
+    pub fn new(sample_rate: f64, channels: usize, window_seconds: f64,
+               integration: Integration) -> RmsMeter {
+        assert!(channels > 0, "a meter needs at least one channel");
+        let window_samples = sample_rate * window_seconds;
+        assert!(window_samples >= 1.0, "the window must be at least one sample long");
+
+        let (window_length, history) = match integration {
+            Integration::Rectangular => {
+                let window_length = window_samples.round() as usize;
+                (window_length, vec![0.0; window_length * channels])
+            }
+            Integration::Exponential => (0, Vec::new()),
+        };
+        RmsMeter {
+            full_scale: FullScale::default(),
+            integration,
+            sine_referenced: false,
+            channels,
+            states: vec![0.0; channels],
+            history,
+            window_length,
+            position: 0,
+            coefficient: 1.0 - (-1.0 / window_samples).exp(),
+        }
+    }
+
+    /// Sets the full-scale convention used to normalize integer samples.
+    pub fn set_full_scale(&mut self, full_scale: FullScale) {
+        self.full_scale = full_scale;
+    }
+
+    /// Sets whether readings follow the AES17 convention, which adds
+    /// 3.01 dB so that a full-scale sine wave reads 0 dBFS RMS.
+    pub fn set_sine_referenced(&mut self, sine_referenced: bool) {
+        self.sine_referenced = sine_referenced;
+    }
+
+    /// Returns the number of channels.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Measures a block of interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't contain a whole number of frames.
+    pub fn process<S: Sample>(&mut self, interleaved: &[S]) {
+        assert_eq!(interleaved.len() % self.channels, 0,
+                   "the block must contain a whole number of frames");
+
+        for frame in interleaved.chunks(self.channels) {
+            match self.integration {
+                Integration::Rectangular => self.push_rectangular(frame),
+                Integration::Exponential => {
+                    for (state, &sample) in self.states.iter_mut().zip(frame) {
+                        let sample = sample.to_normalized(self.full_scale);
+                        *state += (sample * sample - *state) * self.coefficient;
+                    }
+                }
+            }
+        }
+    }
+
+    fn push_rectangular<S: Sample>(&mut self, frame: &[S]) {
+        let start = self.position * self.channels;
+        let oldest = &mut self.history[start..start + self.channels];
+        for ((sum, squared), &sample) in self.states.iter_mut().zip(oldest.iter_mut()).zip(frame) {
+            let sample = sample.to_normalized(self.full_scale);
+            *sum += sample * sample - *squared;
+            *squared = sample * sample;
+        }
+
+        self.position += 1;
+        if self.position == self.window_length {
+            self.position = 0;
+            // Recalculate the sums once per window, so that rounding errors
+            // from the running sums can't accumulate.
+            for (channel, sum) in self.states.iter_mut().enumerate() {
+                *sum = self.history.iter().skip(channel).step_by(self.channels).sum();
+            }
+        }
+    }
+
+    /// Returns the current RMS level of a channel, in dBFS.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn level(&self, channel: usize) -> DecibelRatio<f64> {
+        let mean_square = match self.integration {
+            Integration::Rectangular => self.states[channel].max(0.0) / self.window_length as f64,
+            Integration::Exponential => self.states[channel],
+        };
+        let level: DecibelRatio<f64> = PowerRatio(mean_square).into();
+        if self.sine_referenced {
+            level + DecibelRatio(SINE_CREST_FACTOR_DECIBELS)
+        } else {
+            level
+        }
+    }
+
+    /// Forgets all measured samples, as if the meter had only measured
+    /// silence.
+    pub fn reset(&mut self) {
+        for value in self.states.iter_mut().chain(self.history.iter_mut()) {
+            *value = 0.0;
+        }
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(actual: DecibelRatio<f64>, expected: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= 0.001,
+                "{:?} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn test_peak_meter_tracks_block_and_max_peaks() {
+        let mut meter = PeakMeter::new(2);
+        meter.process(&[0.5f64, -0.1, -1.0, 0.2]);
+        assert_close(meter.peak(0), 0.0);
+        assert_close(meter.peak(1), -13.9794);
+
+        meter.process(&[0.25f64, 0.0, -0.125, 0.0]);
+        assert_close(meter.peak(0), -12.0412);
+        assert_eq!(meter.peak(1), DecibelRatio(f64::NEG_INFINITY));
+        assert_close(meter.max_peak(0), 0.0);
+        assert_close(meter.max_peak(1), -13.9794);
+        assert_close(meter.overall_max_peak(), 0.0);
+
+        meter.reset();
+        assert_eq!(meter.max_peak(0), DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(meter.overall_max_peak(), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_peak_meter_accepts_integer_samples() {
+        let mut meter = PeakMeter::with_full_scale(1, FullScale::Negative);
+        meter.process(&[0i16, -32768, 100]);
+        assert_eq!(meter.peak(0), DecibelRatio(0.0));
+
+        let mut meter = PeakMeter::new(1);
+        meter.process(&[1i16, -1]);
+        assert_close(meter.peak(0), -90.3087);
+
+        meter.process(&[128u8, 128]);
+        assert_eq!(meter.peak(0), DecibelRatio(f64::NEG_INFINITY));
+        assert_close(meter.max_peak(0), -90.3087);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_partial_frames_panic() {
+        PeakMeter::new(2).process(&[0.0f32, 0.0, 0.0]);
+    }
+
+    fn sine(frames: usize, amplitude: f64) -> Vec<f64> {
+        (0..frames)
+            .map(|i| amplitude * (i as f64 * 2.0 * ::std::f64::consts::PI * 1000.0 / 48000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_rms_meter_reads_sine_and_square_waves() {
+        for &integration in [Integration::Rectangular, Integration::Exponential].iter() {
+            let mut meter = RmsMeter::new(48000.0, 1, 0.3, integration);
+            meter.process(&sine(48000 * 3, 1.0));
+            assert_close(meter.level(0), -3.0103);
+
+            // AES17 references RMS levels to a sine wave.
+            meter.set_sine_referenced(true);
+            assert_close(meter.level(0), 0.0);
+
+            meter.set_sine_referenced(false);
+            let square: Vec<f32> = (0..48000 * 3).map(|i| if i % 48 < 24 { 0.5 } else { -0.5 }).collect();
+            meter.process(&square);
+            assert_close(meter.level(0), -6.0206);
+        }
+    }
+
+    #[test]
+    fn test_rectangular_window_length() {
+        let mut meter = RmsMeter::new(1000.0, 2, 0.1, Integration::Rectangular);
+        meter.process(&vec![1.0f64; 2 * 50]);
+        // Half the window is full, so the mean square is 0.5.
+        assert_close(meter.level(1), -3.0103);
+
+        meter.process(&vec![1.0f64; 2 * 50]);
+        assert_close(meter.level(0), 0.0);
+
+        meter.process(&vec![0i16; 2 * 100]);
+        assert_eq!(meter.level(0), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_exponential_time_constant() {
+        let mut meter = RmsMeter::new(1000.0, 1, 0.1, Integration::Exponential);
+        meter.process(&vec![1.0f64; 100]);
+        // After one time constant, the mean square reaches 1 - 1/e.
+        assert_close(meter.level(0), 10.0 * (1.0 - (-1.0f64).exp()).log10());
+
+        meter.reset();
+        assert_eq!(meter.level(0), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    fn sine_at(sample_rate: f64, frequency: f64, phase: f64, frames: usize) -> Vec<f64> {
+        (0..frames)
+            .map(|i| (i as f64 * 2.0 * PI * frequency / sample_rate + phase).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_true_peak_meter_finds_inter_sample_peaks() {
+        // A quarter of the sample rate, shifted by 45 degrees, has samples at
+        // ±0.707 but a true peak of 1.0.
+        let samples = sine_at(48000.0, 12000.0, PI / 4.0, 4800);
+        let mut sample_peak = PeakMeter::new(1);
+        sample_peak.process(&samples);
+        assert_close(sample_peak.peak(0), -3.0103);
+
+        let mut true_peak = TruePeakMeter::new(48000.0, 1);
+        assert_eq!(true_peak.oversampling_factor(), 4);
+        true_peak.process(&samples);
+        let reading = true_peak.peak(0).decibel_value();
+        assert!(reading > -0.2 && reading < 0.2, "read {} dBTP", reading);
+    }
+
+    #[test]
+    fn test_true_peak_is_never_below_sample_peak() {
+        for &(sample_rate, frequency) in [(48000.0, 1000.0), (44100.0, 6000.0), (22050.0, 3000.0),
+                                          (8000.0, 1000.0)].iter() {
+            let samples = sine_at(sample_rate, frequency, 0.3, 4000);
+            let mut meter = TruePeakMeter::new(sample_rate, 1);
+            meter.process(&samples);
+            let reading = meter.max_peak(0).decibel_value();
+            assert!((-0.001..0.2).contains(&reading), "read {} dBTP at {} Hz", reading, sample_rate);
+        }
+        assert_eq!(TruePeakMeter::new(22050.0, 1).oversampling_factor(), 8);
+        assert_eq!(TruePeakMeter::new(8000.0, 1).oversampling_factor(), 32);
+        assert_eq!(TruePeakMeter::new(192000.0, 1).oversampling_factor(), 4);
+    }
+
+    #[test]
+    fn test_true_peak_meter_tracks_channels_and_positions() {
+        let mut meter = TruePeakMeter::new(48000.0, 2);
+        assert_eq!(meter.max_peak_position(0), None);
+
+        let mut samples = vec![0.0f32; 2 * 1000];
+        samples[2 * 300] = 0.5;
+        samples[2 * 700 + 1] = -0.25;
+        meter.process(&samples);
+        assert_eq!(meter.max_peak_position(0), Some(300));
+        assert_eq!(meter.max_peak_position(1), Some(700));
+        assert_close(meter.max_peak(0), -6.0206);
+        assert_close(meter.overall_max_peak(), -6.0206);
+
+        // Positions keep counting across blocks.
+        let mut samples = vec![0.0f32; 2 * 1000];
+        samples[2 * 10] = 1.0;
+        meter.process(&samples);
+        assert_eq!(meter.max_peak_position(0), Some(1010));
+        assert_close(meter.peak(0), 0.0);
+        assert_eq!(meter.peak(1), DecibelRatio(f64::NEG_INFINITY));
+
+        meter.reset();
+        assert_eq!(meter.max_peak_position(0), None);
+        assert_eq!(meter.overall_max_peak(), DecibelRatio(f64::NEG_INFINITY));
+    }
+}
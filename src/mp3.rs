@@ -0,0 +1,460 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Gain fields for MP3 files.
+//!
+//! MP3 files carry gains in two binary layouts:
+//!
+//! * The ID3v2.4 `RVA2` frame, which has an identification string, then for
+//!   each channel a gain as a signed 16-bit big-endian integer in units of
+//!   1/512 dB, and a peak with a variable number of bits. `Rva2Frame`
+//!   encodes and decodes the body of the frame.
+//! * The ReplayGain fields of the LAME tag: a 32-bit peak, then radio and
+//!   audiophile gains as 16-bit fields with a name code, an originator code,
+//!   a sign bit and a 9-bit gain in units of 0.1 dB. `LameReplayGain`
+//!   encodes and decodes these eight bytes.
+//!
+//! Encoding rounds to the resolution of each layout, and clamps values that
+//! don't fit. Decoding returns `None` for data that doesn't follow the
+//! layout.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::{AmplitudeRatio, DecibelRatio};
+//! use decibel::mp3::{Rva2Adjustment, Rva2Channel, Rva2Frame};
+//!
+//! fn main() {
+//!     let frame = Rva2Frame {
+//!         identification: "track".to_string(),
+//!         adjustments: vec![Rva2Adjustment {
+//!             channel: Rva2Channel::MasterVolume,
+//!             gain: DecibelRatio(-3.0),
+//!             peak: Some(AmplitudeRatio(0.5)),
+//!         }],
+//!     };
+//!     let bytes = frame.encode(16);
+//!     assert_eq!(&bytes[6..], &[0x01, 0xFA, 0x00, 0x10, 0x40, 0x00]);
+//!     assert_eq!(Rva2Frame::decode(&bytes), Some(frame));
+//! }
+//! ```
+
+use std::string::String;
+use std::vec::Vec;
+
+use {AmplitudeRatio, DecibelRatio};
+
+// The RVA2 gain is in units of 1/512 dB.
+const RVA2_STEPS_PER_DECIBEL: f64 = 512.0;
+
+// The LAME gains are in units of 0.1 dB, and the peak is in fixed point with
+// 23 fractional bits.
+const LAME_STEPS_PER_DECIBEL: f64 = 10.0;
+const LAME_MAX_GAIN_STEPS: f64 = 511.0;
+const LAME_PEAK_ONE: f64 = 8_388_608.0;
+
+/// The channel that an RVA2 adjustment applies to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rva2Channel {
+    /// Any other channel, with type 0.
+    Other,
+    /// The master volume, with type 1.
+    MasterVolume,
+    /// The front right channel, with type 2.
+    FrontRight,
+    /// The front left channel, with type 3.
+    FrontLeft,
+    /// The back right channel, with type 4.
+    BackRight,
+    /// The back left channel, with type 5.
+    BackLeft,
+    /// The front centre channel, with type 6.
+    FrontCentre,
+    /// The back centre channel, with type 7.
+    BackCentre,
+    /// The subwoofer, with type 8.
+    Subwoofer,
+}
+
+impl Rva2Channel {
+    /// Returns the channel for a channel type byte, or `None` if the byte
+    /// isn't one defined by ID3v2.4.
+    pub fn from_byte(byte: u8) -> Option<Rva2Channel> {
+        match byte {
+            0 => Some(Rva2Channel::Other),
+            1 => Some(Rva2Channel::MasterVolume),
+            2 => Some(Rva2Channel::FrontRight),
+            3 => Some(Rva2Channel::FrontLeft),
+            4 => Some(Rva2Channel::BackRight),
+            5 => Some(Rva2Channel::BackLeft),
+            6 => Some(Rva2Channel::FrontCentre),
+            7 => Some(Rva2Channel::BackCentre),
+            8 => Some(Rva2Channel::Subwoofer),
+            _ => None,
+        }
+    }
+
+    /// Returns the channel type byte for this channel.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Rva2Channel::Other => 0,
+            Rva2Channel::MasterVolume => 1,
+            Rva2Channel::FrontRight => 2,
+            Rva2Channel::FrontLeft => 3,
+            Rva2Channel::BackRight => 4,
+            Rva2Channel::BackLeft => 5,
+            Rva2Channel::FrontCentre => 6,
+            Rva2Channel::BackCentre => 7,
+            Rva2Channel::Subwoofer => 8,
+        }
+    }
+}
+
+/// The gain and peak of one channel in an RVA2 frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rva2Adjustment {
+    /// The channel the adjustment applies to.
+    pub channel: Rva2Channel,
+    /// The gain, with a resolution of 1/512 dB and a range of -64 dB to just
+    /// under +64 dB.
+    pub gain: DecibelRatio<f64>,
+    /// The peak relative to full scale, if there is one. A peak of n bits
+    /// stores full scale as 2^(n - 1).
+    pub peak: Option<AmplitudeRatio<f64>>,
+}
+
+/// The body of an ID3v2.4 `RVA2` frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rva2Frame {
+    /// The identification string, such as "track" or "album". It's stored
+    /// in ISO-8859-1, so characters outside of it are written as '?'.
+    pub identification: String,
+    /// The adjustments, one for each channel.
+    pub adjustments: Vec<Rva2Adjustment>,
+}
+
+impl Rva2Frame {
+    /// Encodes the body of the frame, writing each peak with the given
+    /// number of bits. Gains are rounded to 1/512 dB, and gains and peaks
+    /// that don't fit are clamped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `peak_bits` isn't between 1 and 32.
+    pub fn encode(&self, peak_bits: u8) -> Vec<u8> {
+        assert!((1..=32).contains(&peak_bits), "the peak must have between 1 and 32 bits");
+
+        let mut bytes: Vec<u8> = self.identification.chars()
+            .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+            .collect();
+        bytes.push(0);
+
+        for adjustment in &self.adjustments {
+            bytes.push(adjustment.channel.to_byte());
+            let gain = clamp(adjustment.gain.decibel_value() * RVA2_STEPS_PER_DECIBEL,
+                             i16::MIN as f64, i16::MAX as f64) as i16;
+            bytes.extend_from_slice(&gain.to_be_bytes());
+
+            match adjustment.peak {
+                Some(peak) => {
+                    let full_scale = 2f64.powi(peak_bits as i32 - 1);
+                    let max = 2f64.powi(peak_bits as i32) - 1.0;
+                    let peak = clamp(peak.amplitude_value() * full_scale, 0.0, max) as u32;
+                    let peak_bytes = (peak_bits as usize).div_ceil(8);
+                    bytes.push(peak_bits);
+                    bytes.extend_from_slice(&peak.to_be_bytes()[4 - peak_bytes..]);
+                }
+                None => bytes.push(0),
+            }
+        }
+        bytes
+    }
+
+    /// Decodes the body of a frame. Returns `None` if the identification
+    /// string isn't terminated, if a channel type isn't defined by ID3v2.4,
+    /// or if an adjustment is cut short.
+    pub fn decode(bytes: &[u8]) -> Option<Rva2Frame> {
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+        let identification = bytes[..end].iter().map(|&byte| byte as char).collect();
+
+        let mut adjustments = Vec::new();
+        let mut rest = &bytes[end + 1..];
+        while !rest.is_empty() {
+            if rest.len() < 4 {
+                return None;
+            }
+            let channel = Rva2Channel::from_byte(rest[0])?;
+            let gain = i16::from_be_bytes([rest[1], rest[2]]) as f64 / RVA2_STEPS_PER_DECIBEL;
+            let peak_bits = rest[3] as usize;
+            let peak_bytes = peak_bits.div_ceil(8);
+            rest = &rest[4..];
+            if rest.len() < peak_bytes {
+                return None;
+            }
+
+            let peak = if peak_bits == 0 {
+                None
+            } else {
+                let value = rest[..peak_bytes].iter().fold(0.0, |value, &byte| value * 256.0 + byte as f64);
+                Some(AmplitudeRatio(value / 2f64.powi(peak_bits as i32 - 1)))
+            };
+            rest = &rest[peak_bytes..];
+            adjustments.push(Rva2Adjustment { channel, gain: DecibelRatio(gain), peak });
+        }
+        Some(Rva2Frame { identification, adjustments })
+    }
+}
+
+/// Who set a gain in the LAME tag.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LameOriginator {
+    /// Not specified, with code 0.
+    Unspecified,
+    /// Set by the artist, with code 1.
+    Artist,
+    /// Set by the user, with code 2.
+    User,
+    /// Set automatically by a loudness model, with code 3.
+    Automatic,
+    /// Set by a simple RMS average, with code 4.
+    RmsAverage,
+}
+
+impl LameOriginator {
+    fn from_code(code: u16) -> Option<LameOriginator> {
+        match code {
+            0 => Some(LameOriginator::Unspecified),
+            1 => Some(LameOriginator::Artist),
+            2 => Some(LameOriginator::User),
+            3 => Some(LameOriginator::Automatic),
+            4 => Some(LameOriginator::RmsAverage),
+            _ => None,
+        }
+    }
+
+    fn to_code(self) -> u16 {
+        match self {
+            LameOriginator::Unspecified => 0,
+            LameOriginator::Artist => 1,
+            LameOriginator::User => 2,
+            LameOriginator::Automatic => 3,
+            LameOriginator::RmsAverage => 4,
+        }
+    }
+}
+
+/// A radio or audiophile gain in the LAME tag.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LameGain {
+    /// Who set the gain.
+    pub originator: LameOriginator,
+    /// The gain, with a resolution of 0.1 dB and a range of -51.1 dB to
+    /// +51.1 dB.
+    pub gain: DecibelRatio<f64>,
+}
+
+// The name codes of the two gain fields.
+const LAME_RADIO: u16 = 1;
+const LAME_AUDIOPHILE: u16 = 2;
+
+impl LameGain {
+    fn encode(&self, name: u16) -> [u8; 2] {
+        let gain = self.gain.decibel_value();
+        let steps = clamp(gain.abs() * LAME_STEPS_PER_DECIBEL, 0.0, LAME_MAX_GAIN_STEPS) as u16;
+        let sign = if gain < 0.0 && steps != 0 { 1 } else { 0 };
+        (name << 13 | self.originator.to_code() << 10 | sign << 9 | steps).to_be_bytes()
+    }
+
+    // Returns the name code and the gain, or `None` if the field isn't set.
+    fn decode(bytes: [u8; 2]) -> Option<(u16, LameGain)> {
+        let field = u16::from_be_bytes(bytes);
+        let name = field >> 13;
+        if name != LAME_RADIO && name != LAME_AUDIOPHILE {
+            return None;
+        }
+        let originator = LameOriginator::from_code(field >> 10 & 0b111)?;
+        let mut gain = (field & 0x1FF) as f64 / LAME_STEPS_PER_DECIBEL;
+        if field & 0x200 != 0 {
+            gain = -gain;
+        }
+        Some((name, LameGain { originator, gain: DecibelRatio(gain) }))
+    }
+}
+
+/// The ReplayGain fields of the LAME tag: the peak, followed by the radio
+/// and audiophile gains.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LameReplayGain {
+    /// The peak relative to full scale, if there is one. It's stored in
+    /// fixed point with 23 fractional bits, which is what LAME writes, and
+    /// zero means that there's no peak.
+    pub peak: Option<AmplitudeRatio<f64>>,
+    /// The radio, or track, gain.
+    pub radio: Option<LameGain>,
+    /// The audiophile, or album, gain.
+    pub audiophile: Option<LameGain>,
+}
+
+impl LameReplayGain {
+    /// Encodes the eight bytes of the fields. Gains are rounded to 0.1 dB,
+    /// and gains and peaks that don't fit are clamped.
+    pub fn encode(&self) -> [u8; 8] {
+        let peak = match self.peak {
+            Some(peak) => clamp(peak.amplitude_value() * LAME_PEAK_ONE, 0.0, u32::MAX as f64) as u32,
+            None => 0,
+        };
+        let radio = self.radio.map_or([0; 2], |gain| gain.encode(LAME_RADIO));
+        let audiophile = self.audiophile.map_or([0; 2], |gain| gain.encode(LAME_AUDIOPHILE));
+
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&peak.to_be_bytes());
+        bytes[4..6].copy_from_slice(&radio);
+        bytes[6..].copy_from_slice(&audiophile);
+        bytes
+    }
+
+    /// Decodes the eight bytes of the fields. Each gain goes where its name
+    /// code says, whichever field it's in, and a field that isn't set or
+    /// has a reserved code decodes to `None`.
+    pub fn decode(bytes: [u8; 8]) -> LameReplayGain {
+        let peak = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mut gains = LameReplayGain {
+            peak: if peak == 0 { None } else { Some(AmplitudeRatio(peak as f64 / LAME_PEAK_ONE)) },
+            radio: None,
+            audiophile: None,
+        };
+        for field in &[[bytes[4], bytes[5]], [bytes[6], bytes[7]]] {
+            match LameGain::decode(*field) {
+                Some((LAME_RADIO, gain)) => gains.radio = Some(gain),
+                Some((_, gain)) => gains.audiophile = Some(gain),
+                None => {}
+            }
+        }
+        gains
+    }
+}
+
+// Rounds a value and clamps it into a range.
+fn clamp(value: f64, min: f64, max: f64) -> f64 {
+    value.round().max(min).min(max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn adjustment(channel: Rva2Channel, gain: f64, peak: Option<f64>) -> Rva2Adjustment {
+        Rva2Adjustment { channel, gain: DecibelRatio(gain), peak: peak.map(AmplitudeRatio) }
+    }
+
+    #[test]
+    fn test_rva2_encode() {
+        let frame = Rva2Frame {
+            identification: "album".to_string(),
+            adjustments: vec![
+                adjustment(Rva2Channel::MasterVolume, -3.0, Some(0.5)),
+                adjustment(Rva2Channel::Subwoofer, 2.5, None),
+            ],
+        };
+        assert_eq!(frame.encode(16), vec![
+            b'a', b'l', b'b', b'u', b'm', 0x00,
+            0x01, 0xFA, 0x00, 0x10, 0x40, 0x00,
+            0x08, 0x05, 0x00, 0x00,
+        ]);
+        // A peak with a number of bits that isn't a whole number of bytes is
+        // padded with zeroes in its most significant bits.
+        assert_eq!(&frame.encode(12)[6..12], &[0x01, 0xFA, 0x00, 0x0C, 0x04, 0x00]);
+    }
+
+    #[test]
+    fn test_rva2_decode() {
+        let bytes = [
+            b't', b'r', b'a', b'c', b'k', 0x00,
+            0x03, 0x00, 0x01, 0x08, 0xFF,
+            0x02, 0x80, 0x00, 0x18, 0x80, 0x00, 0x00,
+        ];
+        let frame = Rva2Frame::decode(&bytes).unwrap();
+        assert_eq!(frame.identification, "track");
+        assert_eq!(frame.adjustments, vec![
+            adjustment(Rva2Channel::FrontLeft, 1.0 / 512.0, Some(255.0 / 128.0)),
+            adjustment(Rva2Channel::FrontRight, -64.0, Some(1.0)),
+        ]);
+
+        assert_eq!(Rva2Frame::decode(b"track\0").unwrap().adjustments, vec![]);
+        assert_eq!(Rva2Frame::decode(b"track"), None);
+        assert_eq!(Rva2Frame::decode(b"track\0\x01\x00\x00"), None);
+        assert_eq!(Rva2Frame::decode(b"track\0\x01\x00\x00\x10\x40"), None);
+        assert_eq!(Rva2Frame::decode(b"track\0\x09\x00\x00\x00"), None);
+    }
+
+    #[test]
+    fn test_rva2_rounding_and_clamping() {
+        let frame = Rva2Frame {
+            identification: "Caf\u{e9} \u{263a}".to_string(),
+            adjustments: vec![
+                adjustment(Rva2Channel::Other, 100.0, Some(3.0)),
+                adjustment(Rva2Channel::Other, -100.0, Some(-1.0)),
+                adjustment(Rva2Channel::Other, -6.0206, Some(0.25)),
+            ],
+        };
+        let decoded = Rva2Frame::decode(&frame.encode(16)).unwrap();
+        assert_eq!(decoded.identification, "Caf\u{e9} ?");
+        assert_eq!(decoded.adjustments[0], adjustment(Rva2Channel::Other, 32767.0 / 512.0, Some(65535.0 / 32768.0)));
+        assert_eq!(decoded.adjustments[1], adjustment(Rva2Channel::Other, -64.0, Some(0.0)));
+        assert_eq!(decoded.adjustments[2], adjustment(Rva2Channel::Other, -3083.0 / 512.0, Some(0.25)));
+    }
+
+    #[test]
+    fn test_lame_encode() {
+        let gains = LameReplayGain {
+            peak: Some(AmplitudeRatio(0.5)),
+            radio: Some(LameGain { originator: LameOriginator::Automatic, gain: DecibelRatio(-3.7) }),
+            audiophile: Some(LameGain { originator: LameOriginator::User, gain: DecibelRatio(1.5) }),
+        };
+        // Radio: 001 011 1 000100101. Audiophile: 010 010 0 000001111.
+        assert_eq!(gains.encode(), [0x00, 0x40, 0x00, 0x00, 0x2E, 0x25, 0x48, 0x0F]);
+
+        let empty = LameReplayGain { peak: None, radio: None, audiophile: None };
+        assert_eq!(empty.encode(), [0; 8]);
+    }
+
+    #[test]
+    fn test_lame_decode() {
+        let gains = LameReplayGain::decode([0x00, 0x80, 0x00, 0x00, 0x2E, 0x25, 0x48, 0x0F]);
+        assert_eq!(gains.peak, Some(AmplitudeRatio(1.0)));
+        assert_eq!(gains.radio, Some(LameGain { originator: LameOriginator::Automatic, gain: DecibelRatio(-3.7) }));
+        assert_eq!(gains.audiophile, Some(LameGain { originator: LameOriginator::User, gain: DecibelRatio(1.5) }));
+
+        // Fields are placed by their name code, and reserved codes are ignored.
+        let gains = LameReplayGain::decode([0, 0, 0, 0, 0x48, 0x0F, 0x7C, 0x00]);
+        assert_eq!(gains.peak, None);
+        assert_eq!(gains.radio, None);
+        assert_eq!(gains.audiophile, Some(LameGain { originator: LameOriginator::User, gain: DecibelRatio(1.5) }));
+    }
+
+    #[test]
+    fn test_lame_rounding_and_clamping() {
+        let gains = LameReplayGain {
+            peak: Some(AmplitudeRatio(1.2345678)),
+            radio: Some(LameGain { originator: LameOriginator::Artist, gain: DecibelRatio(-60.0) }),
+            audiophile: Some(LameGain { originator: LameOriginator::RmsAverage, gain: DecibelRatio(-0.04) }),
+        };
+        let decoded = LameReplayGain::decode(gains.encode());
+        assert!((decoded.peak.unwrap().amplitude_value() - 1.2345678).abs() < 1e-6);
+        assert_eq!(decoded.radio.unwrap().gain, DecibelRatio(-51.1));
+        // A gain that rounds to zero isn't written with the sign bit.
+        assert_eq!(gains.encode()[6..], [0x50, 0x00]);
+        assert_eq!(decoded.audiophile.unwrap().gain, DecibelRatio(0.0));
+    }
+}
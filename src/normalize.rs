@@ -0,0 +1,302 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Peak and loudness normalization of buffers.
+//!
+//! Each function measures a buffer of interleaved samples, works out the gain
+//! that brings it to the target, applies the gain in place, and returns a
+//! `NormalizationReport` with what it measured and did.
+//!
+//! * `normalize_peak()` normalizes the sample peak, in dBFS.
+//! * `normalize_true_peak()` normalizes the true peak, in dBTP.
+//! * `normalize_loudness()` normalizes the integrated loudness, in LUFS.
+//!
+//! Peak and loudness normalization can lower the gain to keep the true peak
+//! under a ceiling. True-peak normalization doesn't need one, since its
+//! target is already a true-peak level.
+//!
+//! Integer samples are normalized with the default full-scale convention, and
+//! saturate if the gain takes them past full scale.
+//!
+//! When the level can't be measured, the buffer is left as it is and the
+//! report has no gain. That happens for silence, and for loudness that the
+//! gating of BS.1770 leaves nothing of: buffers shorter than one 400 ms
+//! gating block, and audio quieter than -70 LUFS.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::normalize::normalize_peak;
+//!
+//! fn main() {
+//!     let mut samples = [0.25f32, -0.5, 0.125];
+//!     let report = normalize_peak(&mut samples, 48000.0, 1, DecibelRatio(0.0), None);
+//!
+//!     assert!((report.gain.unwrap().decibel_value() - 6.0206).abs() < 0.001);
+//!     assert!((samples[1] + 1.0).abs() < 1e-6);
+//! }
+//! ```
+
+use loudness::LoudnessMeter;
+use meter::TruePeakMeter;
+use pcm::{self, FullScale, Sample};
+use {AmplitudeRatio, DecibelRatio};
+
+/// What a normalization measured and did.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NormalizationReport {
+    /// The level measured before normalizing: the sample peak in dBFS, the
+    /// true peak in dBTP, or the integrated loudness in LUFS. This is
+    /// negative infinity if the level couldn't be measured.
+    pub measured: DecibelRatio<f64>,
+    /// The gain that was applied, or `None` if the level couldn't be measured
+    /// and the buffer was left as it is.
+    pub gain: Option<DecibelRatio<f64>>,
+    /// Whether the true-peak ceiling lowered the gain below what the target
+    /// called for.
+    pub limited_by_ceiling: bool,
+    /// The peak after normalizing: the sample peak in dBFS for peak
+    /// normalization without a ceiling, and the true peak in dBTP otherwise.
+    /// This is predicted from the peak before normalizing, so it doesn't
+    /// include any rounding or saturation of integer samples. If there's no
+    /// gain, this is the peak of the unchanged buffer, which may be above
+    /// negative infinity even though the level couldn't be measured.
+    pub predicted_peak: DecibelRatio<f64>,
+}
+
+/// Multiplies every sample by a gain in place.
+pub fn apply_gain<S: Sample>(samples: &mut [S], gain: DecibelRatio<f64>) {
+    let gain = AmplitudeRatio::from(gain).amplitude_value();
+    let full_scale = FullScale::default();
+    for sample in samples.iter_mut() {
+        *sample = S::from_normalized(sample.to_normalized(full_scale) * gain, full_scale);
+    }
+}
+
+// Works out the gain that takes a measured level to the target, with an
+// optional ceiling for the peak, applies it, and reports it.
+fn normalize<S: Sample>(samples: &mut [S], measured: DecibelRatio<f64>, target: DecibelRatio<f64>,
+                        peak: DecibelRatio<f64>, ceiling: Option<DecibelRatio<f64>>) -> NormalizationReport {
+    if measured.decibel_value() == f64::NEG_INFINITY {
+        return NormalizationReport { measured, gain: None, limited_by_ceiling: false, predicted_peak: peak };
+    }
+
+    let mut gain = target - measured;
+    let mut limited_by_ceiling = false;
+    if let Some(ceiling) = ceiling {
+        if (peak + gain).decibel_value() > ceiling.decibel_value() {
+            gain = ceiling - peak;
+            limited_by_ceiling = true;
+        }
+    }
+    apply_gain(samples, gain);
+    NormalizationReport { measured, gain: Some(gain), limited_by_ceiling, predicted_peak: peak + gain }
+}
+
+/// Normalizes the sample peak of a buffer of interleaved samples to a target
+/// level in dBFS. With a `true_peak_ceiling` in dBTP, the gain is lowered if
+/// it would take the true peak over the ceiling. The sample rate and the
+/// number of channels are only used to measure the true peak for the
+/// ceiling.
+///
+/// # Panics
+///
+/// With a ceiling, panics under the same conditions as `TruePeakMeter::new()`
+/// and `TruePeakMeter::process()`.
+pub fn normalize_peak<S: Sample>(samples: &mut [S], sample_rate: f64, channels: usize,
+                                 target: DecibelRatio<f64>,
+                                 true_peak_ceiling: Option<DecibelRatio<f64>>) -> NormalizationReport {
+    let sample_peak = pcm::peak_dbfs(samples, FullScale::default());
+    let peak = match true_peak_ceiling {
+        Some(_) => true_peak(samples, sample_rate, channels),
+        None => sample_peak,
+    };
+    normalize(samples, sample_peak, target, peak, true_peak_ceiling)
+}
+
+/// Normalizes the true peak of a buffer of interleaved samples to a target
+/// level in dBTP. This takes no ceiling, since a ceiling on the true peak
+/// would only be a lower target.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `TruePeakMeter::new()` and
+/// `TruePeakMeter::process()`.
+pub fn normalize_true_peak<S: Sample>(samples: &mut [S], sample_rate: f64, channels: usize,
+                                      target: DecibelRatio<f64>) -> NormalizationReport {
+    let peak = true_peak(samples, sample_rate, channels);
+    normalize(samples, peak, target, peak, None)
+}
+
+/// Normalizes the integrated loudness of a buffer of interleaved samples to a
+/// target level in LUFS. With a `true_peak_ceiling` in dBTP, the gain is
+/// lowered if it would take the true peak over the ceiling, and the buffer
+/// ends up quieter than the target.
+///
+/// A buffer whose loudness is gated out entirely, because it's shorter than
+/// one 400 ms gating block or quieter than -70 LUFS, is left as it is, and
+/// the report has no gain.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `LoudnessMeter::new()` and
+/// `LoudnessMeter::process()`.
+pub fn normalize_loudness<S: Sample>(samples: &mut [S], sample_rate: f64, channels: usize,
+                                     target: DecibelRatio<f64>,
+                                     true_peak_ceiling: Option<DecibelRatio<f64>>) -> NormalizationReport {
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.process(samples);
+    let peak = true_peak(samples, sample_rate, channels);
+    normalize(samples, meter.integrated(), target, peak, true_peak_ceiling)
+}
+
+fn true_peak<S: Sample>(samples: &[S], sample_rate: f64, channels: usize) -> DecibelRatio<f64> {
+    let mut meter = TruePeakMeter::new(sample_rate, channels);
+    meter.process(samples);
+    meter.overall_max_peak()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::PI;
+    use std::vec::Vec;
+
+    fn assert_close(actual: DecibelRatio<f64>, expected: f64, tolerance: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= tolerance,
+                "{:?} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    // A stereo 1 kHz sine at 48 kHz with the given peak level in dBFS.
+    fn sine(dbfs: f64, seconds: f64) -> Vec<f64> {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+        let mut samples = Vec::new();
+        for i in 0..(48000.0 * seconds) as usize {
+            let sample = amplitude * (2.0 * PI * i as f64 / 48.0).sin();
+            samples.push(sample);
+            samples.push(sample);
+        }
+        samples
+    }
+
+    #[test]
+    fn test_normalize_peak() {
+        let mut samples = sine(-12.0, 0.1);
+        let report = normalize_peak(&mut samples, 48000.0, 2, DecibelRatio(-1.0), None);
+        assert_close(report.measured, -12.0, 1e-9);
+        assert_close(report.gain.unwrap(), 11.0, 1e-9);
+        assert_close(report.predicted_peak, -1.0, 1e-9);
+        assert!(!report.limited_by_ceiling);
+        assert_close(pcm::peak_dbfs(&samples, FullScale::default()), -1.0, 1e-9);
+    }
+
+    #[test]
+    fn test_normalize_integer_samples() {
+        let mut samples = [8192i16, -16384, 0];
+        let report = normalize_peak(&mut samples, 48000.0, 1, DecibelRatio(0.0), None);
+        assert_close(report.gain.unwrap(), 6.02, 0.01);
+        assert_eq!(samples, [16384, -32767, 0]);
+    }
+
+    #[test]
+    fn test_normalize_true_peak() {
+        // A 12 kHz sine sampled at 45 degrees has a true peak 3 dB above its
+        // sample peak.
+        let mut samples: Vec<f32> = (0..4800)
+            .map(|i| 0.5 * (PI / 4.0 + 2.0 * PI * i as f64 / 4.0).sin() as f32)
+            .collect();
+        let report = normalize_true_peak(&mut samples, 48000.0, 1, DecibelRatio(-1.0));
+        assert_close(report.measured, -6.02, 0.1);
+        assert_close(report.predicted_peak, -1.0, 1e-9);
+        assert_close(pcm::peak_dbfs(&samples, FullScale::default()), -4.01, 0.1);
+    }
+
+    #[test]
+    fn test_normalize_peak_with_a_ceiling() {
+        // The same 12 kHz sine, which peak normalization to 0 dBFS would take
+        // to a true peak of +3 dBTP.
+        let sine: Vec<f32> = (0..4800)
+            .map(|i| 0.5 * (PI / 4.0 + 2.0 * PI * i as f64 / 4.0).sin() as f32)
+            .collect();
+        let mut samples = sine.clone();
+        let report = normalize_peak(&mut samples, 48000.0, 1, DecibelRatio(0.0), Some(DecibelRatio(-1.0)));
+        assert!(report.limited_by_ceiling);
+        assert_close(report.measured, -9.03, 0.1);
+        assert_close(report.gain.unwrap(), 5.02, 0.1);
+        assert_close(report.predicted_peak, -1.0, 1e-9);
+        assert_close(pcm::peak_dbfs(&samples, FullScale::default()), -4.01, 0.1);
+
+        // A ceiling that the target doesn't reach changes nothing.
+        let mut samples = sine.clone();
+        let report = normalize_peak(&mut samples, 48000.0, 1, DecibelRatio(-6.0), Some(DecibelRatio(-1.0)));
+        assert!(!report.limited_by_ceiling);
+        assert_close(report.gain.unwrap(), 3.03, 0.1);
+        assert_close(report.predicted_peak, -3.0, 0.1);
+    }
+
+    #[test]
+    fn test_normalize_loudness() {
+        let mut samples = sine(-33.0, 2.0);
+        let report = normalize_loudness(&mut samples, 48000.0, 2, DecibelRatio(-23.0), None);
+        assert_close(report.measured, -33.0, 0.1);
+        assert_close(report.gain.unwrap(), 10.0, 0.1);
+        assert_close(report.predicted_peak, -23.0, 0.1);
+
+        let mut meter = LoudnessMeter::new(48000.0, 2);
+        meter.process(&samples);
+        assert_close(meter.integrated(), -23.0, 0.01);
+    }
+
+    #[test]
+    fn test_normalize_loudness_with_a_ceiling() {
+        let mut samples = sine(-33.0, 2.0);
+        let report = normalize_loudness(&mut samples, 48000.0, 2, DecibelRatio(0.0), Some(DecibelRatio(-1.0)));
+        assert!(report.limited_by_ceiling);
+        assert_close(report.gain.unwrap(), 32.0, 0.1);
+        assert_close(report.predicted_peak, -1.0, 1e-9);
+        assert_close(pcm::peak_dbfs(&samples, FullScale::default()), -1.0, 0.1);
+    }
+
+    #[test]
+    fn test_silence_is_left_alone() {
+        let mut samples = [0.0f32; 4800];
+        let report = normalize_loudness(&mut samples, 48000.0, 1, DecibelRatio(-23.0), Some(DecibelRatio(-1.0)));
+        assert_eq!(report.measured, DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(report.gain, None);
+        assert_eq!(report.predicted_peak, DecibelRatio(f64::NEG_INFINITY));
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+
+        let report = normalize_peak(&mut samples, 48000.0, 1, DecibelRatio(-1.0), None);
+        assert_eq!(report.gain, None);
+    }
+
+    #[test]
+    fn test_gated_out_loudness_is_left_alone() {
+        // 300 ms is shorter than one gating block, so there's no loudness
+        // even though the buffer isn't silent.
+        let mut samples = sine(-20.0, 0.3);
+        let original = samples.clone();
+        let report = normalize_loudness(&mut samples, 48000.0, 2, DecibelRatio(-23.0), None);
+        assert_eq!(report.measured, DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(report.gain, None);
+        assert!(!report.limited_by_ceiling);
+        assert_close(report.predicted_peak, -20.0, 0.1);
+        assert_eq!(samples, original);
+
+        // So is audio under the absolute gate of -70 LUFS.
+        let mut samples = sine(-75.0, 1.0);
+        assert_eq!(normalize_loudness(&mut samples, 48000.0, 2, DecibelRatio(-23.0), None).gain, None);
+    }
+}
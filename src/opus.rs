@@ -0,0 +1,148 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Gains for Opus files, in Q7.8 fixed point.
+//!
+//! Opus stores gains as signed 16-bit integers in units of 1/256 dB: the
+//! output gain in the `OpusHead` header, and the `R128_TRACK_GAIN` and
+//! `R128_ALBUM_GAIN` tags. The tags are relative to a loudness of -23 LUFS,
+//! and apply on top of the output gain.
+//!
+//! Conversions round to the nearest step of 1/256 dB, and saturate at the
+//! ends of the 16-bit range, from -128 dB to just under +128 dB. A
+//! `Q78Gain` reports whether that happened.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::opus::r128_gain;
+//!
+//! fn main() {
+//!     // A track at -16 LUFS, in a file with no output gain.
+//!     let gain = r128_gain(DecibelRatio(-16.0), 0);
+//!     assert_eq!(gain.value, -1792);
+//!     assert!(!gain.saturated);
+//!     let tag = format!("R128_TRACK_GAIN={}", gain.value);
+//!     assert_eq!(tag, "R128_TRACK_GAIN=-1792");
+//! }
+//! ```
+
+use math;
+use DecibelRatio;
+
+/// The loudness that the R128 gain tags are relative to, in LUFS.
+pub const R128_REFERENCE_LOUDNESS: f64 = -23.0;
+
+// The number of Q7.8 steps per decibel.
+const STEPS_PER_DECIBEL: f64 = 256.0;
+
+/// A gain in Q7.8 fixed point, in units of 1/256 dB.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Q78Gain {
+    /// The gain, in units of 1/256 dB.
+    pub value: i16,
+    /// Whether the gain was outside the range of Q7.8 and was clamped to the
+    /// nearest end of it.
+    pub saturated: bool,
+}
+
+/// Converts a gain into Q7.8 fixed point, rounding to the nearest step and
+/// saturating at the ends of the range. NaN converts to zero, and is reported
+/// as saturated.
+pub fn to_q7_8(gain: DecibelRatio<f64>) -> Q78Gain {
+    let steps = math::round(gain.decibel_value() * STEPS_PER_DECIBEL);
+    if steps.is_nan() {
+        Q78Gain { value: 0, saturated: true }
+    } else if steps > i16::MAX as f64 {
+        Q78Gain { value: i16::MAX, saturated: true }
+    } else if steps < i16::MIN as f64 {
+        Q78Gain { value: i16::MIN, saturated: true }
+    } else {
+        Q78Gain { value: steps as i16, saturated: false }
+    }
+}
+
+/// Converts a gain in Q7.8 fixed point into decibels. This is exact.
+#[inline]
+pub fn from_q7_8(value: i16) -> DecibelRatio<f64> {
+    DecibelRatio(value as f64 / STEPS_PER_DECIBEL)
+}
+
+/// Returns the output gain for the `OpusHead` header that brings a measured
+/// loudness to a target loudness, both in LUFS.
+pub fn output_gain(loudness: DecibelRatio<f64>, target: DecibelRatio<f64>) -> Q78Gain {
+    to_q7_8(target - loudness)
+}
+
+/// Returns the value of an `R128_TRACK_GAIN` or `R128_ALBUM_GAIN` tag for a
+/// measured loudness in LUFS, in a file whose header has the given output
+/// gain in Q7.8. The tag brings the loudness to -23 LUFS together with the
+/// output gain. Silence needs an infinite gain, so it saturates.
+pub fn r128_gain(loudness: DecibelRatio<f64>, output_gain: i16) -> Q78Gain {
+    to_q7_8(DecibelRatio(R128_REFERENCE_LOUDNESS) - loudness - from_q7_8(output_gain))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        for value in i16::MIN..=i16::MAX {
+            assert_eq!(to_q7_8(from_q7_8(value)), Q78Gain { value, saturated: false });
+        }
+
+        // Any gain in range comes back within half a step.
+        let mut gain = -127.9;
+        while gain < 127.9 {
+            let back = from_q7_8(to_q7_8(DecibelRatio(gain)).value).decibel_value();
+            assert!((back - gain).abs() <= 0.5 / 256.0 + 1e-12, "{} came back as {}", gain, back);
+            gain += 0.0137;
+        }
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(to_q7_8(DecibelRatio(1.0)).value, 256);
+        assert_eq!(to_q7_8(DecibelRatio(-6.02)).value, -1541);
+        assert_eq!(to_q7_8(DecibelRatio(0.5 / 256.0)).value, 1);
+        assert_eq!(to_q7_8(DecibelRatio(-0.5 / 256.0)).value, -1);
+        assert_eq!(to_q7_8(DecibelRatio(0.49 / 256.0)).value, 0);
+    }
+
+    #[test]
+    fn test_saturation() {
+        assert_eq!(to_q7_8(DecibelRatio(32767.49 / 256.0)), Q78Gain { value: 32767, saturated: false });
+        assert_eq!(to_q7_8(DecibelRatio(32767.5 / 256.0)), Q78Gain { value: 32767, saturated: true });
+        assert_eq!(to_q7_8(DecibelRatio(-128.0)), Q78Gain { value: -32768, saturated: false });
+        assert_eq!(to_q7_8(DecibelRatio(-32768.5 / 256.0)), Q78Gain { value: -32768, saturated: true });
+        assert_eq!(to_q7_8(DecibelRatio(1000.0)), Q78Gain { value: 32767, saturated: true });
+        assert_eq!(to_q7_8(DecibelRatio(f64::NEG_INFINITY)), Q78Gain { value: -32768, saturated: true });
+        assert_eq!(to_q7_8(DecibelRatio(f64::NAN)), Q78Gain { value: 0, saturated: true });
+    }
+
+    #[test]
+    fn test_r128_gains() {
+        assert_eq!(r128_gain(DecibelRatio(-23.0), 0), Q78Gain { value: 0, saturated: false });
+        assert_eq!(r128_gain(DecibelRatio(-16.0), 0).value, -1792);
+        assert_eq!(r128_gain(DecibelRatio(-30.5), 0).value, 1920);
+        // The tag applies on top of the output gain.
+        assert_eq!(r128_gain(DecibelRatio(-16.0), -1792).value, 0);
+        assert_eq!(r128_gain(DecibelRatio(-16.0), 256).value, -2048);
+        assert_eq!(r128_gain(DecibelRatio(f64::NEG_INFINITY), 0), Q78Gain { value: 32767, saturated: true });
+
+        assert_eq!(output_gain(DecibelRatio(-14.0), DecibelRatio(-23.0)).value, -2304);
+    }
+}
@@ -0,0 +1,357 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions between integer PCM samples and dBFS.
+//!
+//! Integer samples have one more negative code than positive codes, so there
+//! are two ways to normalize them. `FullScale` chooses between them. The
+//! supported sample formats are unsigned 8-bit offset binary (`u8`), signed
+//! 16-bit (`i16`), signed 24-bit (`I24`, which can be unpacked from 3-byte
+//! buffers) and signed 32-bit (`i32`). Floating-point samples (`f32` and
+//! `f64`) are accepted too, so code can be written once for every format.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::pcm::{self, FullScale};
+//!
+//! fn main() {
+//!     // +1 or -1 in a 16-bit signed sample is approximately -90.3 dBFS.
+//!     let lsb: DecibelRatio<f64> = pcm::to_dbfs(1i16, FullScale::Positive);
+//!     assert!(lsb.decibel_value() > -90.31 && lsb.decibel_value() < -90.30);
+//!
+//!     // The peak of a raw buffer, without any normalization code.
+//!     let buffer = [0i16, 16384, -8192, 100];
+//!     let peak: DecibelRatio<f64> = pcm::peak_dbfs(&buffer, FullScale::Negative);
+//!     assert!(peak.decibel_value() > -6.03 && peak.decibel_value() < -6.01);
+//! }
+//! ```
+
+use {AmplitudeRatio, DecibelRatio, Float};
+use math;
+
+/// The convention used to map integer samples onto full scale.
+///
+/// A 16-bit sample ranges from -32768 to +32767. Dividing by 32767 or by
+/// 32768 gives slightly different dBFS readings for the extreme codes, about
+/// 0.00027 dB apart for 16-bit samples and less for wider ones.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FullScale {
+    /// Full scale is the largest positive code, such as 32767.
+    ///
+    /// This follows AES17: a full-scale sine wave, which peaks at the largest
+    /// positive code and its negation, reads 0 dBFS. The most negative code
+    /// reads slightly above 0 dBFS, so a full-scale square wave that reaches
+    /// it reads about +0.00027 dBFS for 16-bit samples. This is the default.
+    #[default]
+    Positive,
+    /// Full scale is the magnitude of the most negative code, such as 32768.
+    ///
+    /// A full-scale square wave that reaches the most negative code reads
+    /// 0 dBFS, and a full-scale sine wave reads slightly below 0 dBFS. This
+    /// matches the common practice of converting to floating point by
+    /// dividing by a power of two.
+    Negative,
+}
+
+/// A signed 24-bit sample, stored sign-extended in an `i32`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct I24(i32);
+
+impl I24 {
+    /// The smallest value of a 24-bit sample.
+    pub const MIN: I24 = I24(-8_388_608);
+
+    /// The largest value of a 24-bit sample.
+    pub const MAX: I24 = I24(8_388_607);
+
+    /// Creates a 24-bit sample.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` doesn't fit in 24 bits.
+    #[inline]
+    pub fn new(value: i32) -> I24 {
+        assert!((I24::MIN.0..=I24::MAX.0).contains(&value),
+                "{} doesn't fit in a 24-bit sample", value);
+        I24(value)
+    }
+
+    /// Returns the value of the sample.
+    #[inline]
+    pub fn value(self) -> i32 {
+        self.0
+    }
+
+    /// Creates a sample from its packed little-endian representation.
+    #[inline]
+    pub fn from_le_bytes(bytes: [u8; 3]) -> I24 {
+        I24(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8)
+    }
+
+    /// Creates a sample from its packed big-endian representation.
+    #[inline]
+    pub fn from_be_bytes(bytes: [u8; 3]) -> I24 {
+        I24(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8)
+    }
+
+    /// Returns the packed little-endian representation of the sample.
+    #[inline]
+    pub fn to_le_bytes(self) -> [u8; 3] {
+        let bytes = self.0.to_le_bytes();
+        [bytes[0], bytes[1], bytes[2]]
+    }
+
+    /// Returns the packed big-endian representation of the sample.
+    #[inline]
+    pub fn to_be_bytes(self) -> [u8; 3] {
+        let bytes = self.0.to_be_bytes();
+        [bytes[1], bytes[2], bytes[3]]
+    }
+}
+
+/// Unpacks a buffer of packed little-endian 24-bit samples.
+///
+/// Any trailing bytes that don't make up a whole sample are ignored.
+pub fn unpack_i24_le(bytes: &[u8]) -> impl Iterator<Item = I24> + '_ {
+    bytes.chunks_exact(3).map(|sample| I24::from_le_bytes([sample[0], sample[1], sample[2]]))
+}
+
+/// Unpacks a buffer of packed big-endian 24-bit samples.
+///
+/// Any trailing bytes that don't make up a whole sample are ignored.
+pub fn unpack_i24_be(bytes: &[u8]) -> impl Iterator<Item = I24> + '_ {
+    bytes.chunks_exact(3).map(|sample| I24::from_be_bytes([sample[0], sample[1], sample[2]]))
+}
+
+/// A PCM sample format that can be normalized to full scale.
+pub trait Sample: Copy {
+    /// Converts the sample into a signed, normalized value, where ±1.0 is
+    /// full scale.
+    fn to_normalized(self, full_scale: FullScale) -> f64;
+
+    /// Converts a signed, normalized value into a sample. Integer formats
+    /// round to the nearest code and saturate at the ends of their range;
+    /// floating-point formats keep the value as it is.
+    fn from_normalized(value: f64, full_scale: FullScale) -> Self;
+}
+
+macro_rules! impl_integer_sample {
+    ($S: ty, $min: expr, $max: expr, $to_code: expr, $from_code: expr) => {
+        impl Sample for $S {
+            #[inline]
+            fn to_normalized(self, full_scale: FullScale) -> f64 {
+                let to_code: fn($S) -> i64 = $to_code;
+                to_code(self) as f64 / full_scale_code(full_scale, $min, $max)
+            }
+
+            #[inline]
+            fn from_normalized(value: f64, full_scale: FullScale) -> $S {
+                let from_code: fn(i64) -> $S = $from_code;
+                let code = math::round(value * full_scale_code(full_scale, $min, $max));
+                // NaN saturates to zero, which is silence for every format.
+                let code = if code.is_nan() {
+                    0
+                } else if code <= $min as f64 {
+                    $min
+                } else if code >= $max as f64 {
+                    $max
+                } else {
+                    code as i64
+                };
+                from_code(code)
+            }
+        }
+    }
+}
+
+macro_rules! impl_float_sample {
+    ($S: ty) => {
+        // Floating-point samples are already normalized, so the full-scale
+        // convention doesn't apply.
+        impl Sample for $S {
+            #[inline]
+            fn to_normalized(self, _: FullScale) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn from_normalized(value: f64, _: FullScale) -> $S {
+                value as $S
+            }
+        }
+    }
+}
+
+#[inline]
+fn full_scale_code(full_scale: FullScale, min: i64, max: i64) -> f64 {
+    match full_scale {
+        FullScale::Positive => max as f64,
+        FullScale::Negative => -(min as f64),
+    }
+}
+
+impl_integer_sample!(u8, -128, 127, |sample| sample as i64 - 128, |code| (code + 128) as u8);
+impl_integer_sample!(i16, -32_768, 32_767, |sample| sample as i64, |code| code as i16);
+impl_integer_sample!(I24, -8_388_608, 8_388_607, |sample| sample.0 as i64, |code| I24(code as i32));
+impl_integer_sample!(i32, -2_147_483_648, 2_147_483_647, |sample| sample as i64, |code| code as i32);
+impl_float_sample!(f32);
+impl_float_sample!(f64);
+
+/// Returns the magnitude of a sample as an amplitude relative to full scale.
+#[inline]
+pub fn to_amplitude<S: Sample, T: Float>(sample: S, full_scale: FullScale) -> AmplitudeRatio<T> {
+    let normalized = sample.to_normalized(full_scale);
+    AmplitudeRatio(T::from_f64(if normalized < 0.0 { -normalized } else { normalized }))
+}
+
+/// Returns the level of a sample in dBFS. Silence is negative infinity.
+#[inline]
+pub fn to_dbfs<S: Sample, T: Float>(sample: S, full_scale: FullScale) -> DecibelRatio<T> {
+    to_amplitude::<S, T>(sample, full_scale).into()
+}
+
+/// Returns the positive sample closest to an amplitude relative to full
+/// scale, saturating at the largest positive code.
+#[inline]
+pub fn from_amplitude<S: Sample, T: Float>(amplitude: AmplitudeRatio<T>, full_scale: FullScale) -> S {
+    S::from_normalized(amplitude.amplitude_value().to_f64(), full_scale)
+}
+
+/// Returns the positive sample closest to a level in dBFS, saturating at the
+/// largest positive code.
+#[inline]
+pub fn from_dbfs<S: Sample, T: Float>(decibels: DecibelRatio<T>, full_scale: FullScale) -> S {
+    from_amplitude(AmplitudeRatio::from(decibels), full_scale)
+}
+
+/// Returns the largest sample magnitude in a buffer, as an amplitude relative
+/// to full scale.
+pub fn peak_amplitude<S: Sample, T: Float>(samples: &[S], full_scale: FullScale) -> AmplitudeRatio<T> {
+    let peak = samples.iter().fold(0.0, |peak: f64, &sample| {
+        let normalized = sample.to_normalized(full_scale);
+        let magnitude = if normalized < 0.0 { -normalized } else { normalized };
+        if magnitude > peak { magnitude } else { peak }
+    });
+    AmplitudeRatio(T::from_f64(peak))
+}
+
+/// Returns the largest sample magnitude in a buffer, in dBFS. An empty or
+/// silent buffer is negative infinity.
+pub fn peak_dbfs<S: Sample, T: Float>(samples: &[S], full_scale: FullScale) -> DecibelRatio<T> {
+    peak_amplitude::<S, T>(samples, full_scale).into()
+}
+
+#[cfg(test)]
+#[allow(clippy::excessive_precision)]
+mod test {
+    use super::*;
+    use std::vec::Vec;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= 0.0001,
+                "{} is not close to {}", actual, expected);
+    }
+
+    fn dbfs<S: Sample>(sample: S, full_scale: FullScale) -> f64 {
+        to_dbfs::<S, f64>(sample, full_scale).decibel_value()
+    }
+
+    #[test]
+    fn test_sixteen_bit_samples() {
+        // +1 or -1 in a 16-bit signed sample should be approximately -90.3 dBFS.
+        assert_close(dbfs(1i16, FullScale::Positive), -90.30873362169473);
+        assert_close(dbfs(-1i16, FullScale::Positive), -90.30873362169473);
+        assert_close(dbfs(1i16, FullScale::Negative), -90.30899869919435);
+
+        assert_eq!(dbfs(32767i16, FullScale::Positive), 0.0);
+        assert_eq!(dbfs(-32768i16, FullScale::Negative), 0.0);
+        assert_close(dbfs(-32768i16, FullScale::Positive), 0.000265);
+        assert_close(dbfs(32767i16, FullScale::Negative), -0.000265);
+        assert_eq!(dbfs(0i16, FullScale::Positive), f64::NEG_INFINITY);
+
+        let lsb: i16 = from_dbfs(DecibelRatio(-90.30873362169473), FullScale::Positive);
+        assert_eq!(lsb, 1);
+        let half: i16 = from_dbfs(DecibelRatio(-6.02059991327962), FullScale::Negative);
+        assert_eq!(half, 16384);
+        let clipped: i16 = from_dbfs(DecibelRatio(6.0), FullScale::Positive);
+        assert_eq!(clipped, 32767);
+        let silent: i16 = from_dbfs(DecibelRatio(f64::NEG_INFINITY), FullScale::Positive);
+        assert_eq!(silent, 0);
+    }
+
+    #[test]
+    fn test_eight_bit_offset_binary_samples() {
+        assert_eq!(dbfs(128u8, FullScale::Positive), f64::NEG_INFINITY);
+        assert_eq!(dbfs(255u8, FullScale::Positive), 0.0);
+        assert_eq!(dbfs(0u8, FullScale::Negative), 0.0);
+        assert_close(dbfs(192u8, FullScale::Negative), -6.0206);
+
+        assert_eq!(u8::from_normalized(0.0, FullScale::Positive), 128);
+        assert_eq!(u8::from_normalized(-2.0, FullScale::Negative), 0);
+        assert_eq!(u8::from_normalized(1.0, FullScale::Positive), 255);
+    }
+
+    #[test]
+    fn test_twenty_four_bit_samples() {
+        let max = I24::from_le_bytes([0xff, 0xff, 0x7f]);
+        assert_eq!(max, I24::MAX);
+        assert_eq!(I24::from_be_bytes([0x80, 0x00, 0x00]), I24::MIN);
+        assert_eq!(I24::from_le_bytes([0xff, 0xff, 0xff]).value(), -1);
+        assert_eq!(I24::new(-2).to_le_bytes(), [0xfe, 0xff, 0xff]);
+        assert_eq!(I24::new(0x123456).to_be_bytes(), [0x12, 0x34, 0x56]);
+
+        assert_eq!(dbfs(max, FullScale::Positive), 0.0);
+        assert_close(dbfs(I24::new(1), FullScale::Positive), -138.4738);
+
+        let packed = [0x00, 0x00, 0x40, 0x00, 0x00, 0xe0, 0x01];
+        let samples: Vec<I24> = unpack_i24_le(&packed).collect();
+        assert_eq!(samples, vec![I24::new(0x400000), I24::new(-0x200000)]);
+        let peak: DecibelRatio<f64> = peak_dbfs(&samples, FullScale::Negative);
+        assert_close(peak.decibel_value(), -6.0206);
+    }
+
+    #[test]
+    fn test_thirty_two_bit_samples() {
+        assert_eq!(dbfs(i32::MAX, FullScale::Positive), 0.0);
+        assert_eq!(dbfs(i32::MIN, FullScale::Negative), 0.0);
+        assert_eq!(i32::from_normalized(1.5, FullScale::Positive), i32::MAX);
+        assert_eq!(i32::from_normalized(-1.5, FullScale::Positive), i32::MIN);
+        assert_eq!(i32::from_normalized(-1.0, FullScale::Negative), i32::MIN);
+    }
+
+    #[test]
+    fn test_full_scale_sine_and_square_waves() {
+        // A full-scale sine quantized with the AES17 convention peaks at the
+        // largest positive code, and reads 0 dBFS.
+        let sine: Vec<i16> = (0..480)
+            .map(|i| (i as f64 * 2.0 * ::std::f64::consts::PI / 48.0).sin())
+            .map(|value| i16::from_normalized(value, FullScale::Positive))
+            .collect();
+        let peak: DecibelRatio<f64> = peak_dbfs(&sine, FullScale::Positive);
+        assert_eq!(peak.decibel_value(), 0.0);
+
+        // A full-scale square wave reaches the most negative code, so it only
+        // reads 0 dBFS with the other convention.
+        let square = [32767i16, -32768, 32767, -32768];
+        let peak: DecibelRatio<f64> = peak_dbfs(&square, FullScale::Negative);
+        assert_eq!(peak.decibel_value(), 0.0);
+        let peak: DecibelRatio<f64> = peak_dbfs(&square, FullScale::Positive);
+        assert!(peak.decibel_value() > 0.0);
+
+        let silence: DecibelRatio<f32> = peak_dbfs::<i16, f32>(&[], FullScale::Positive);
+        assert_eq!(silence.decibel_value(), f32::NEG_INFINITY);
+    }
+}
@@ -0,0 +1,265 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! ReplayGain 2.0 track and album gain.
+//!
+//! ReplayGain 2.0 measures integrated loudness as specified by ITU-R
+//! BS.1770, and stores the gain that brings it to a reference of -18 LUFS,
+//! along with the sample peak.
+//!
+//! A `TrackAnalyzer` measures one track. Album gain comes from
+//! `album_gain()`, which gates the 400 ms blocks of all the album's tracks
+//! together, as if they were one long track, rather than averaging the
+//! track gains. `tags()` formats the results as `REPLAYGAIN_*` tags.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::replaygain::TrackAnalyzer;
+//!
+//! fn main() {
+//!     // One second of a 1 kHz sine at -23 dBFS in both channels.
+//!     let amplitude = 10f64.powf(-23.0 / 20.0);
+//!     let mut samples = Vec::new();
+//!     for i in 0..48000 {
+//!         let sample = amplitude * (i as f64 * 2.0 * std::f64::consts::PI / 48.0).sin();
+//!         samples.push(sample);
+//!         samples.push(sample);
+//!     }
+//!
+//!     let mut analyzer = TrackAnalyzer::new(48000.0, 2);
+//!     analyzer.process(&samples);
+//!     let track = analyzer.finish().replay_gain();
+//!     let gain = track.gain.decibel_value();
+//!     assert!(gain > 4.9 && gain < 5.1);
+//!     println!("REPLAYGAIN_TRACK_GAIN={}", track.format_gain());
+//! }
+//! ```
+
+use std::string::String;
+use std::vec::Vec;
+
+use loudness::{gated_loudness, LoudnessMeter, RELATIVE_GATE};
+use meter::PeakMeter;
+use pcm::Sample;
+use {AmplitudeRatio, DecibelRatio};
+
+/// The loudness that ReplayGain 2.0 brings tracks to, in LUFS.
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+/// Returns the ReplayGain for a loudness in LUFS. Silence gets no gain.
+pub fn gain_for_loudness(loudness: DecibelRatio<f64>) -> DecibelRatio<f64> {
+    if loudness.decibel_value() == f64::NEG_INFINITY {
+        DecibelRatio(0.0)
+    } else {
+        DecibelRatio(REFERENCE_LOUDNESS) - loudness
+    }
+}
+
+/// A ReplayGain gain and peak, for a track or an album.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReplayGain {
+    /// The gain that brings the audio to the reference loudness.
+    pub gain: DecibelRatio<f64>,
+    /// The sample peak, relative to full scale.
+    pub peak: AmplitudeRatio<f64>,
+}
+
+impl ReplayGain {
+    /// Formats the gain for a `REPLAYGAIN_*_GAIN` tag, such as "-6.54 dB".
+    pub fn format_gain(&self) -> String {
+        let gain = format!("{:.2}", self.gain.decibel_value());
+        // Don't write tiny negative gains as "-0.00".
+        if gain == "-0.00" { "0.00 dB".to_string() } else { gain + " dB" }
+    }
+
+    /// Formats the peak for a `REPLAYGAIN_*_PEAK` tag, such as "0.988553".
+    pub fn format_peak(&self) -> String {
+        format!("{:.6}", self.peak.amplitude_value())
+    }
+}
+
+/// Returns the `REPLAYGAIN_*` tags for a track, and for its album if there
+/// is one, as names and values.
+pub fn tags(track: &ReplayGain, album: Option<&ReplayGain>) -> Vec<(&'static str, String)> {
+    let mut tags = vec![
+        ("REPLAYGAIN_TRACK_GAIN", track.format_gain()),
+        ("REPLAYGAIN_TRACK_PEAK", track.format_peak()),
+    ];
+    if let Some(album) = album {
+        tags.push(("REPLAYGAIN_ALBUM_GAIN", album.format_gain()));
+        tags.push(("REPLAYGAIN_ALBUM_PEAK", album.format_peak()));
+    }
+    tags.push(("REPLAYGAIN_REFERENCE_LOUDNESS", format!("{:.2} LUFS", REFERENCE_LOUDNESS)));
+    tags
+}
+
+/// The measurements of one track.
+#[derive(Clone, Debug)]
+pub struct TrackAnalysis {
+    loudness: DecibelRatio<f64>,
+    peak: AmplitudeRatio<f64>,
+    gating_blocks: Vec<f64>,
+}
+
+impl TrackAnalysis {
+    /// Returns the track's integrated loudness, in LUFS.
+    pub fn loudness(&self) -> DecibelRatio<f64> {
+        self.loudness
+    }
+
+    /// Returns the track's gain and peak.
+    pub fn replay_gain(&self) -> ReplayGain {
+        ReplayGain { gain: gain_for_loudness(self.loudness), peak: self.peak }
+    }
+}
+
+/// Measures one track for ReplayGain.
+#[derive(Clone, Debug)]
+pub struct TrackAnalyzer {
+    loudness: LoudnessMeter,
+    peak: PeakMeter,
+}
+
+impl TrackAnalyzer {
+    /// Creates an analyzer for interleaved blocks with the given sample rate
+    /// and number of channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as `LoudnessMeter::new()`.
+    pub fn new(sample_rate: f64, channels: usize) -> TrackAnalyzer {
+        TrackAnalyzer {
+            loudness: LoudnessMeter::new(sample_rate, channels),
+            peak: PeakMeter::new(channels),
+        }
+    }
+
+    /// Measures a block of interleaved samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block doesn't contain a whole number of frames.
+    pub fn process<S: Sample>(&mut self, interleaved: &[S]) {
+        self.loudness.process(interleaved);
+        self.peak.process(interleaved);
+    }
+
+    /// Finishes the track and returns its measurements.
+    pub fn finish(self) -> TrackAnalysis {
+        TrackAnalysis {
+            loudness: self.loudness.integrated(),
+            peak: self.peak.overall_max_peak().into(),
+            gating_blocks: self.loudness.gating_blocks().to_vec(),
+        }
+    }
+}
+
+/// Returns the album gain and peak for a set of tracks. The loudness is
+/// gated over the blocks of all the tracks together, and the peak is the
+/// largest track peak.
+pub fn album_gain(tracks: &[TrackAnalysis]) -> ReplayGain {
+    let blocks = tracks.iter().flat_map(|track| track.gating_blocks.iter());
+    let peak = tracks.iter().fold(0.0, |peak: f64, track| peak.max(track.peak.amplitude_value()));
+    ReplayGain {
+        gain: gain_for_loudness(gated_loudness(blocks, RELATIVE_GATE)),
+        peak: AmplitudeRatio(peak),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_close(actual: DecibelRatio<f64>, expected: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= 0.05,
+                "{:?} is not close to {}", actual, expected);
+    }
+
+    // Analyzes five seconds of a stereo 1 kHz sine with the given peak level
+    // in dBFS, which has the same loudness in LUFS.
+    fn track(dbfs: f64) -> TrackAnalysis {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+        let mut analyzer = TrackAnalyzer::new(48000.0, 2);
+        for second in 0..5 {
+            let mut samples = Vec::with_capacity(96000);
+            for i in 0..48000 {
+                let sample = amplitude * (2.0 * PI * (second * 48000 + i) as f64 / 48.0).sin();
+                samples.push(sample);
+                samples.push(sample);
+            }
+            analyzer.process(&samples);
+        }
+        analyzer.finish()
+    }
+
+    #[test]
+    fn test_track_gain() {
+        let analysis = track(-23.0);
+        assert_close(analysis.loudness(), -23.0);
+        let gain = analysis.replay_gain();
+        assert_close(gain.gain, 5.0);
+        assert!((gain.peak.amplitude_value() - 10f64.powf(-23.0 / 20.0)).abs() < 1e-6);
+
+        assert_close(track(-10.0).replay_gain().gain, -8.0);
+    }
+
+    #[test]
+    fn test_album_gain_gates_all_tracks_together() {
+        // Equally long tracks at -20 and -30 LUFS have a combined loudness of
+        // -22.6 LUFS, not the -25 LUFS that averaging would give.
+        let album = album_gain(&[track(-20.0), track(-30.0)]);
+        assert_close(album.gain, 4.6);
+        assert!((album.peak.amplitude_value() - 0.1).abs() < 1e-6);
+
+        // A quiet track falls under the album's relative gate.
+        let album = album_gain(&[track(-20.0), track(-45.0)]);
+        assert_close(album.gain, 2.0);
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut analyzer = TrackAnalyzer::new(44100.0, 1);
+        analyzer.process(&[0i16; 44100]);
+        let analysis = analyzer.finish();
+        assert_eq!(analysis.loudness(), DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(analysis.replay_gain(), ReplayGain { gain: DecibelRatio(0.0), peak: AmplitudeRatio(0.0) });
+        assert_eq!(album_gain(&[]).gain, DecibelRatio(0.0));
+    }
+
+    #[test]
+    fn test_formatting() {
+        let gain = ReplayGain { gain: DecibelRatio(-6.537), peak: AmplitudeRatio(0.988_553_2) };
+        assert_eq!(gain.format_gain(), "-6.54 dB");
+        assert_eq!(gain.format_peak(), "0.988553");
+        let gain = ReplayGain { gain: DecibelRatio(-0.001), peak: AmplitudeRatio(1.0) };
+        assert_eq!(gain.format_gain(), "0.00 dB");
+        assert_eq!(gain.format_peak(), "1.000000");
+    }
+
+    #[test]
+    fn test_tags() {
+        let track = ReplayGain { gain: DecibelRatio(2.5), peak: AmplitudeRatio(0.5) };
+        let album = ReplayGain { gain: DecibelRatio(1.25), peak: AmplitudeRatio(0.75) };
+        assert_eq!(tags(&track, Some(&album)), vec![
+            ("REPLAYGAIN_TRACK_GAIN", "2.50 dB".to_string()),
+            ("REPLAYGAIN_TRACK_PEAK", "0.500000".to_string()),
+            ("REPLAYGAIN_ALBUM_GAIN", "1.25 dB".to_string()),
+            ("REPLAYGAIN_ALBUM_PEAK", "0.750000".to_string()),
+            ("REPLAYGAIN_REFERENCE_LOUDNESS", "-18.00 LUFS".to_string()),
+        ]);
+        assert_eq!(tags(&track, None).len(), 3);
+    }
+}
@@ -0,0 +1,334 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Smoothing of gain changes, to avoid zipper noise.
+//!
+//! A `GainSmoother` takes target gains in decibels, such as the position of a
+//! volume fader, and produces a per-sample `AmplitudeRatio` that moves
+//! smoothly towards the target. It doesn't allocate, and the per-sample work
+//! is a multiplication or a multiply-add, so it can run in a real-time audio
+//! callback.
+//!
+//! Ramps that are linear in decibels can't reach negative infinity, so ramps
+//! to and from mute go as far as `MUTE_FLOOR_DECIBELS` and then jump the
+//! rest of the way, which is inaudible.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::smoothing::{GainSmoother, Smoothing};
+//!
+//! fn main() {
+//!     // Fade from unity gain to -12 dB over 10 ms.
+//!     let mut smoother = GainSmoother::new(48000.0, Smoothing::FixedDuration { seconds: 0.01 },
+//!                                          DecibelRatio(0.0f32));
+//!     smoother.set_target(DecibelRatio(-12.0));
+//!
+//!     let mut buffer = [1.0f32; 480];
+//!     smoother.process(&mut buffer);
+//!     assert!(buffer[0] < 1.0 && buffer[0] > 0.99);
+//!     assert!((buffer[479] - 0.2512).abs() < 0.0001);
+//!     assert!(!smoother.is_smoothing());
+//! }
+//! ```
+
+use math;
+use {AmplitudeRatio, DecibelRatio, Float};
+
+/// The level that ramps which are linear in decibels use in place of
+/// negative infinity, in dB.
+pub const MUTE_FLOOR_DECIBELS: f64 = -100.0;
+
+// One-pole smoothing jumps to the target once it is this close, in
+// amplitude, so that it settles instead of approaching forever.
+const ONE_POLE_SNAP: f64 = 1e-6;
+
+/// How a `GainSmoother` moves towards its target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Smoothing {
+    /// Ramps linearly in decibels at a fixed rate, so larger changes take
+    /// longer.
+    LinearInDecibels {
+        /// The rate of change, in dB per second, which must be positive.
+        decibels_per_second: f64,
+    },
+    /// Smooths the amplitude with a one-pole low-pass filter, so the gain
+    /// covers 63% of the remaining distance every time constant.
+    OnePole {
+        /// The time constant, in seconds.
+        time_constant: f64,
+    },
+    /// Ramps linearly in decibels over a fixed duration, whatever the size
+    /// of the change.
+    FixedDuration {
+        /// The duration of each ramp, in seconds.
+        seconds: f64,
+    },
+}
+
+/// Smooths changes in gain, producing a gain for every sample.
+#[derive(Copy, Clone, Debug)]
+pub struct GainSmoother<T: Copy> {
+    smoothing: Smoothing,
+    sample_rate: f64,
+    target: DecibelRatio<T>,
+    target_amplitude: T,
+    current: T,
+    // For ramps: the factor to apply every sample, and the samples left.
+    factor: T,
+    remaining: u64,
+    // For one-pole smoothing: the filter coefficient.
+    coefficient: T,
+}
+
+impl<T: Float> GainSmoother<T> {
+    /// Creates a smoother for the given sample rate that starts out at the
+    /// `initial` gain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sample rate or the smoothing's rate isn't positive, or
+    /// if the smoothing's time constant or duration is negative.
+    pub fn new(sample_rate: f64, smoothing: Smoothing, initial: DecibelRatio<T>) -> GainSmoother<T> {
+        assert!(sample_rate > 0.0, "the sample rate must be positive");
+        let coefficient = match smoothing {
+            Smoothing::LinearInDecibels { decibels_per_second } => {
+                assert!(decibels_per_second > 0.0, "the rate must be positive");
+                0.0
+            }
+            Smoothing::OnePole { time_constant } => {
+                assert!(time_constant >= 0.0, "the time constant can't be negative");
+                1.0 - math::exp(-1.0 / (time_constant * sample_rate))
+            }
+            Smoothing::FixedDuration { seconds } => {
+                assert!(seconds >= 0.0, "the duration can't be negative");
+                0.0
+            }
+        };
+        let amplitude = AmplitudeRatio::from(initial).amplitude_value();
+        GainSmoother {
+            smoothing,
+            sample_rate,
+            target: initial,
+            target_amplitude: amplitude,
+            current: amplitude,
+            factor: T::from_f64(1.0),
+            remaining: 0,
+            coefficient: T::from_f64(coefficient),
+        }
+    }
+
+    /// Returns the gain the smoother is moving towards.
+    #[inline]
+    pub fn target(&self) -> DecibelRatio<T> {
+        self.target
+    }
+
+    /// Returns the most recent gain.
+    #[inline]
+    pub fn current(&self) -> AmplitudeRatio<T> {
+        AmplitudeRatio(self.current)
+    }
+
+    /// Returns whether the gain is still moving towards the target.
+    #[inline]
+    pub fn is_smoothing(&self) -> bool {
+        self.current != self.target_amplitude
+    }
+
+    /// Sets a new target gain, which the smoother starts moving towards from
+    /// wherever it is now.
+    pub fn set_target(&mut self, target: DecibelRatio<T>) {
+        self.target = target;
+        self.target_amplitude = AmplitudeRatio::from(target).amplitude_value();
+
+        let start = DecibelRatio::from(AmplitudeRatio(self.current.to_f64())).decibel_value();
+        let start = start.max(MUTE_FLOOR_DECIBELS);
+        let end = target.decibel_value().to_f64().max(MUTE_FLOOR_DECIBELS);
+        let distance = end - start;
+        let samples = match self.smoothing {
+            Smoothing::OnePole { .. } => return,
+            Smoothing::LinearInDecibels { decibels_per_second } => {
+                // Round up, so the ramp never moves faster than the rate, but
+                // don't let rounding errors add a sample.
+                math::ceil(distance.abs() / decibels_per_second * self.sample_rate - 1e-9)
+            }
+            Smoothing::FixedDuration { seconds } => math::round(seconds * self.sample_rate),
+        };
+
+        if distance == 0.0 || samples < 1.0 {
+            self.current = self.target_amplitude;
+            self.remaining = 0;
+        } else {
+            let start_amplitude = AmplitudeRatio::from(DecibelRatio(start)).amplitude_value();
+            let factor = AmplitudeRatio::from(DecibelRatio(distance / samples)).amplitude_value();
+            self.current = T::from_f64(start_amplitude);
+            self.factor = T::from_f64(factor);
+            self.remaining = samples as u64;
+        }
+    }
+
+    /// Jumps straight to a gain, without smoothing.
+    pub fn set_immediately(&mut self, gain: DecibelRatio<T>) {
+        self.target = gain;
+        self.target_amplitude = AmplitudeRatio::from(gain).amplitude_value();
+        self.current = self.target_amplitude;
+        self.remaining = 0;
+    }
+
+    /// Advances by one sample and returns the gain for it.
+    #[inline]
+    pub fn next_gain(&mut self) -> AmplitudeRatio<T> {
+        if let Smoothing::OnePole { .. } = self.smoothing {
+            let difference = self.target_amplitude - self.current;
+            let snap = T::from_f64(ONE_POLE_SNAP);
+            self.current = if difference < snap && -difference < snap {
+                self.target_amplitude
+            } else {
+                self.current + difference * self.coefficient
+            };
+        } else if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target_amplitude
+            } else {
+                self.current * self.factor
+            };
+        }
+        AmplitudeRatio(self.current)
+    }
+
+    /// Applies the smoothed gain to a block of samples in place.
+    pub fn process(&mut self, samples: &mut [T]) {
+        for sample in samples.iter_mut() {
+            *sample = *sample * self.next_gain().amplitude_value();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_decibels(gain: AmplitudeRatio<f64>) -> f64 {
+        DecibelRatio::from(gain).decibel_value()
+    }
+
+    #[test]
+    fn test_fixed_duration_ramp() {
+        let mut smoother = GainSmoother::new(1000.0, Smoothing::FixedDuration { seconds: 0.01 }, DecibelRatio(0.0));
+        assert!(!smoother.is_smoothing());
+        smoother.set_target(DecibelRatio(-20.0));
+        assert!(smoother.is_smoothing());
+
+        for step in 1..10 {
+            let gain = to_decibels(smoother.next_gain());
+            assert!((gain + 2.0 * step as f64).abs() < 1e-9, "step {} is at {} dB", step, gain);
+        }
+        let target = AmplitudeRatio::from(DecibelRatio(-20.0));
+        assert_eq!(smoother.next_gain(), target);
+        assert!(!smoother.is_smoothing());
+        assert_eq!(smoother.next_gain(), target);
+    }
+
+    #[test]
+    fn test_linear_in_decibels_ramp() {
+        // 6 dB at 120 dB per second takes 50 ms.
+        let mut smoother = GainSmoother::new(1000.0, Smoothing::LinearInDecibels { decibels_per_second: 120.0 },
+                                             DecibelRatio(-6.0));
+        smoother.set_target(DecibelRatio(0.0));
+        let mut samples = 0;
+        while smoother.is_smoothing() {
+            let gain = to_decibels(smoother.next_gain());
+            samples += 1;
+            assert!((gain - (-6.0 + 0.12 * samples as f64)).abs() < 1e-9);
+        }
+        assert_eq!(samples, 50);
+        assert_eq!(smoother.current(), AmplitudeRatio(1.0));
+    }
+
+    #[test]
+    fn test_ramps_to_and_from_mute() {
+        let mut smoother = GainSmoother::new(1000.0, Smoothing::FixedDuration { seconds: 0.1 }, DecibelRatio(0.0));
+        smoother.set_target(DecibelRatio(f64::NEG_INFINITY));
+        let gains: [f64; 100] = core::array::from_fn(|_| smoother.next_gain().amplitude_value());
+        assert!((to_decibels(AmplitudeRatio(gains[49])) + 50.0).abs() < 1e-9);
+        assert!((to_decibels(AmplitudeRatio(gains[98])) + 99.0).abs() < 1e-9);
+        assert_eq!(gains[99], 0.0);
+
+        smoother.set_target(DecibelRatio(-40.0));
+        let first = to_decibels(smoother.next_gain());
+        assert!((first + 99.4).abs() < 1e-9, "the ramp starts at {} dB", first);
+        for _ in 1..100 {
+            smoother.next_gain();
+        }
+        assert_eq!(smoother.current(), AmplitudeRatio::from(DecibelRatio(-40.0)));
+    }
+
+    #[test]
+    fn test_one_pole_smoothing() {
+        // A time constant of 100 samples.
+        let mut smoother = GainSmoother::new(1000.0, Smoothing::OnePole { time_constant: 0.1 }, DecibelRatio(f64::NEG_INFINITY));
+        smoother.set_target(DecibelRatio(0.0));
+        for _ in 0..100 {
+            smoother.next_gain();
+        }
+        assert!((smoother.current().amplitude_value() - (1.0 - (-1.0f64).exp())).abs() < 1e-3);
+
+        for _ in 0..2000 {
+            smoother.next_gain();
+        }
+        assert!(!smoother.is_smoothing());
+        assert_eq!(smoother.current(), AmplitudeRatio(1.0));
+
+        smoother.set_target(DecibelRatio(f64::NEG_INFINITY));
+        for _ in 0..2000 {
+            smoother.next_gain();
+        }
+        assert_eq!(smoother.current(), AmplitudeRatio(0.0));
+    }
+
+    #[test]
+    fn test_zero_length_smoothing_jumps() {
+        let mut smoother = GainSmoother::new(48000.0, Smoothing::FixedDuration { seconds: 0.0 }, DecibelRatio(0.0));
+        smoother.set_target(DecibelRatio(-6.0));
+        assert!(!smoother.is_smoothing());
+
+        let mut smoother = GainSmoother::new(48000.0, Smoothing::OnePole { time_constant: 0.0 }, DecibelRatio(0.0));
+        smoother.set_target(DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(smoother.next_gain(), AmplitudeRatio(0.0));
+    }
+
+    #[test]
+    fn test_process_in_f32() {
+        let mut smoother = GainSmoother::new(1000.0, Smoothing::FixedDuration { seconds: 0.004 }, DecibelRatio(0.0f32));
+        smoother.set_target(DecibelRatio(-12.0));
+        let mut samples = [0.5f32; 6];
+        smoother.process(&mut samples);
+        assert!((samples[0] - 0.5 * 0.7079).abs() < 1e-4);
+        assert!((samples[3] - 0.5 * 0.2512).abs() < 1e-4);
+        assert_eq!(samples[3], samples[5]);
+
+        smoother.set_immediately(DecibelRatio(0.0));
+        assert_eq!(smoother.current(), AmplitudeRatio(1.0));
+        assert_eq!(smoother.target(), DecibelRatio(0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_rate_panics() {
+        GainSmoother::new(1000.0, Smoothing::LinearInDecibels { decibels_per_second: 0.0 }, DecibelRatio(0.0f64));
+    }
+}
@@ -0,0 +1,404 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A sound level meter as specified by IEC 61672-1.
+//!
+//! The `SoundLevelMeter` applies a frequency weighting and a time weighting
+//! to a microphone signal, and reports:
+//!
+//! * the time-weighted sound level, such as LAF or LCS,
+//! * the equivalent continuous sound level, such as LAeq,
+//! * the maximum and minimum time-weighted levels, such as LAFmax and LAFmin,
+//! * the peak sound level, such as LCpeak, and
+//! * the sound exposure level, such as LAE.
+//!
+//! Readings are `DbSpl` levels. The meter is calibrated with the sound
+//! pressure level that corresponds to an RMS of 1.0 in normalized samples,
+//! which `full_scale_level_from_calibrator()` works out from a reading of an
+//! acoustic calibrator.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::level::{Dbfs, DbSpl};
+//! use decibel::sound_level::{full_scale_level_from_calibrator, SoundLevelMeter, TimeWeighting};
+//! use decibel::weighting::FrequencyWeighting;
+//!
+//! fn main() {
+//!     // A 94 dB SPL calibrator reads -20 dBFS RMS.
+//!     let full_scale = full_scale_level_from_calibrator(Dbfs::new(-20.0), DbSpl::new(94.0));
+//!     let mut meter = SoundLevelMeter::new(48000.0, FrequencyWeighting::A,
+//!                                          TimeWeighting::Fast, full_scale);
+//!
+//!     // One second of the calibrator tone.
+//!     let amplitude = 10f64.powf(-20.0 / 20.0) * 2f64.sqrt();
+//!     let samples: Vec<f64> = (0..48000)
+//!         .map(|i| amplitude * (i as f64 * 2.0 * std::f64::consts::PI / 48.0).sin())
+//!         .collect();
+//!     meter.process(&samples);
+//!
+//!     let laeq = meter.equivalent_level().decibel_value();
+//!     assert!(laeq > 93.9 && laeq < 94.1);
+//! }
+//! ```
+
+use level::{Dbfs, DbSpl, Gain};
+use pcm::{FullScale, Sample};
+use weighting::{FrequencyWeighting, WeightingFilter};
+use {DecibelRatio, PowerRatio};
+
+// The time-weighted level takes a while to settle after the meter starts, so
+// the maximum and minimum levels ignore the first few time constants.
+const SETTLING_TIME_CONSTANTS: f64 = 5.0;
+
+/// An exponential time weighting from IEC 61672-1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimeWeighting {
+    /// Fast, with a time constant of 125 ms.
+    Fast,
+    /// Slow, with a time constant of 1 s.
+    Slow,
+    /// Impulse, with a time constant of 35 ms, followed by a peak detector
+    /// that decays with a time constant of 1.5 s.
+    Impulse,
+}
+
+impl TimeWeighting {
+    /// Returns the time constants for rising and falling levels, in seconds.
+    /// The rising time constant is that of the exponential average, and a
+    /// longer falling time constant is that of the peak detector after it.
+    pub fn time_constants(self) -> (f64, f64) {
+        match self {
+            TimeWeighting::Fast => (0.125, 0.125),
+            TimeWeighting::Slow => (1.0, 1.0),
+            TimeWeighting::Impulse => (0.035, 1.5),
+        }
+    }
+}
+
+/// Works out a meter's calibration from a reading of an acoustic calibrator:
+/// the calibrator's RMS level in dBFS, measured without sine referencing as
+/// an `RmsMeter` does by default, and its sound pressure level.
+///
+/// Returns the sound pressure level that corresponds to an RMS of 1.0 in
+/// normalized samples.
+pub fn full_scale_level_from_calibrator(reading: Dbfs<f64>, reference: DbSpl<f64>) -> DbSpl<f64> {
+    reference + (Dbfs::new(0.0) - reading)
+}
+
+/// A sound level meter for one channel of microphone samples.
+#[derive(Clone, Debug)]
+pub struct SoundLevelMeter {
+    full_scale: FullScale,
+    full_scale_level: DbSpl<f64>,
+    sample_rate: f64,
+    time_weighting: TimeWeighting,
+    filter: WeightingFilter,
+    coefficient: f64,
+    // How much the peak detector decays each sample, or zero if there's no
+    // peak detector.
+    decay: f64,
+    settling_samples: u64,
+    // The exponential average of the square of the frequency-weighted signal.
+    mean_square: f64,
+    // The time-weighted mean square: the exponential average, held by the
+    // peak detector if there is one.
+    detected: f64,
+    samples: u64,
+    sum_of_squares: f64,
+    // The extremes of the time-weighted mean square once it has settled.
+    max_mean_square: Option<f64>,
+    min_mean_square: Option<f64>,
+    peak: f64,
+}
+
+impl SoundLevelMeter {
+    /// Creates a sound level meter for the given sample rate and weightings.
+    /// `full_scale_level` is the sound pressure level that corresponds to an
+    /// RMS of 1.0 in normalized samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sample rate isn't positive.
+    pub fn new(sample_rate: f64, frequency_weighting: FrequencyWeighting,
+               time_weighting: TimeWeighting, full_scale_level: DbSpl<f64>) -> SoundLevelMeter {
+        let filter = WeightingFilter::new(frequency_weighting, sample_rate);
+        let (rise, fall) = time_weighting.time_constants();
+        SoundLevelMeter {
+            full_scale: FullScale::default(),
+            full_scale_level,
+            sample_rate,
+            time_weighting,
+            filter,
+            coefficient: 1.0 - (-1.0 / (rise * sample_rate)).exp(),
+            decay: if fall > rise { (-1.0 / (fall * sample_rate)).exp() } else { 0.0 },
+            settling_samples: (SETTLING_TIME_CONSTANTS * rise * sample_rate).ceil() as u64,
+            mean_square: 0.0,
+            detected: 0.0,
+            samples: 0,
+            sum_of_squares: 0.0,
+            max_mean_square: None,
+            min_mean_square: None,
+            peak: 0.0,
+        }
+    }
+
+    /// Sets the full-scale convention used to normalize integer samples.
+    pub fn set_full_scale(&mut self, full_scale: FullScale) {
+        self.full_scale = full_scale;
+    }
+
+    /// Sets the sound pressure level that corresponds to an RMS of 1.0 in
+    /// normalized samples.
+    pub fn set_full_scale_level(&mut self, full_scale_level: DbSpl<f64>) {
+        self.full_scale_level = full_scale_level;
+    }
+
+    /// Returns the frequency weighting.
+    pub fn frequency_weighting(&self) -> FrequencyWeighting {
+        self.filter.weighting()
+    }
+
+    /// Returns the time weighting.
+    pub fn time_weighting(&self) -> TimeWeighting {
+        self.time_weighting
+    }
+
+    /// Measures a block of samples.
+    pub fn process<S: Sample>(&mut self, samples: &[S]) {
+        for &sample in samples {
+            let weighted = self.filter.process(sample.to_normalized(self.full_scale));
+            let squared = weighted * weighted;
+            // Choosing the time constant for each squared sample would rectify
+            // the signal, so the average is symmetric, and only the peak
+            // detector after it falls more slowly.
+            self.mean_square += (squared - self.mean_square) * self.coefficient;
+            self.detected = self.mean_square.max(self.detected * self.decay);
+            self.sum_of_squares += squared;
+            self.peak = self.peak.max(weighted.abs());
+            self.samples += 1;
+
+            if self.samples > self.settling_samples {
+                let mean_square = self.detected;
+                self.max_mean_square = Some(self.max_mean_square.map_or(mean_square, |max| max.max(mean_square)));
+                self.min_mean_square = Some(self.min_mean_square.map_or(mean_square, |min| min.min(mean_square)));
+            }
+        }
+    }
+
+    fn level_of(&self, mean_square: f64) -> DbSpl<f64> {
+        self.full_scale_level + Gain::from_ratio(PowerRatio(mean_square).into())
+    }
+
+    /// Returns the current time-weighted sound level.
+    pub fn level(&self) -> DbSpl<f64> {
+        self.level_of(self.detected)
+    }
+
+    /// Returns the equivalent continuous sound level over everything measured
+    /// since the meter was created or reset. This is negative infinity if
+    /// nothing has been measured.
+    pub fn equivalent_level(&self) -> DbSpl<f64> {
+        if self.samples == 0 {
+            return DbSpl::new(f64::NEG_INFINITY);
+        }
+        self.level_of(self.sum_of_squares / self.samples as f64)
+    }
+
+    /// Returns the maximum time-weighted sound level, or `None` if the time
+    /// weighting hasn't settled yet. The level is considered settled after
+    /// five of its rising time constants.
+    pub fn max_level(&self) -> Option<DbSpl<f64>> {
+        self.max_mean_square.map(|mean_square| self.level_of(mean_square))
+    }
+
+    /// Returns the minimum time-weighted sound level, or `None` if the time
+    /// weighting hasn't settled yet.
+    pub fn min_level(&self) -> Option<DbSpl<f64>> {
+        self.min_mean_square.map(|mean_square| self.level_of(mean_square))
+    }
+
+    /// Returns the peak sound level: the largest magnitude of the
+    /// frequency-weighted signal, without time weighting.
+    pub fn peak_level(&self) -> DbSpl<f64> {
+        let peak: DecibelRatio<f64> = PowerRatio(self.peak * self.peak).into();
+        self.full_scale_level + Gain::from_ratio(peak)
+    }
+
+    /// Returns the sound exposure level: the level of a one-second sound with
+    /// the same energy as everything measured since the meter was created or
+    /// reset.
+    pub fn exposure_level(&self) -> DbSpl<f64> {
+        self.level_of(self.sum_of_squares / self.sample_rate)
+    }
+
+    /// Returns how long the meter has measured, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.samples as f64 / self.sample_rate
+    }
+
+    /// Forgets all measured samples.
+    pub fn reset(&mut self) {
+        self.filter.reset();
+        self.mean_square = 0.0;
+        self.detected = 0.0;
+        self.samples = 0;
+        self.sum_of_squares = 0.0;
+        self.max_mean_square = None;
+        self.min_mean_square = None;
+        self.peak = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64::consts::PI;
+    use std::vec::Vec;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn assert_close(actual: DbSpl<f64>, expected: f64, tolerance: f64) {
+        assert!((actual.decibel_value() - expected).abs() <= tolerance,
+                "{:?} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    // Makes a meter where a full-scale sine reads 100 dB SPL.
+    fn meter(frequency_weighting: FrequencyWeighting, time_weighting: TimeWeighting) -> SoundLevelMeter {
+        let full_scale = full_scale_level_from_calibrator(Dbfs::new(-3.010_299_956_639_812), DbSpl::new(100.0));
+        SoundLevelMeter::new(SAMPLE_RATE, frequency_weighting, time_weighting, full_scale)
+    }
+
+    fn tone(frequency: f64, amplitude: f64, seconds: f64) -> Vec<f64> {
+        (0..(seconds * SAMPLE_RATE).round() as usize)
+            .map(|i| amplitude * (2.0 * PI * frequency * i as f64 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f64) -> Vec<f64> {
+        vec![0.0; (seconds * SAMPLE_RATE).round() as usize]
+    }
+
+    #[test]
+    fn test_calibration() {
+        let full_scale = full_scale_level_from_calibrator(Dbfs::new(-20.0), DbSpl::new(94.0));
+        assert_eq!(full_scale, DbSpl::new(114.0));
+
+        let mut meter = meter(FrequencyWeighting::Z, TimeWeighting::Fast);
+        meter.process(&tone(1000.0, 1.0, 2.0));
+        assert_close(meter.level(), 100.0, 0.05);
+        assert_close(meter.equivalent_level(), 100.0, 0.01);
+        assert_close(meter.peak_level(), 103.01, 0.01);
+
+        meter.set_full_scale_level(DbSpl::new(0.0));
+        assert_close(meter.equivalent_level(), -3.01, 0.01);
+    }
+
+    #[test]
+    fn test_tone_burst_response() {
+        // Maximum levels of 4 kHz tone bursts relative to the steady level,
+        // from IEC 61672-1, with class 1 tolerances of ±0.5 dB or better.
+        let cases = [
+            (TimeWeighting::Fast, 0.2, -1.0),
+            (TimeWeighting::Fast, 0.01, -11.1),
+            (TimeWeighting::Slow, 0.5, -4.1),
+            (TimeWeighting::Slow, 0.2, -7.4),
+            (TimeWeighting::Impulse, 0.02, -3.6),
+        ];
+        for &(time_weighting, duration, expected) in cases.iter() {
+            let mut meter = meter(FrequencyWeighting::A, time_weighting);
+            meter.process(&silence(6.0));
+            meter.process(&tone(4000.0, 1.0, duration));
+            meter.process(&silence(1.0));
+            let steady = 100.0 + FrequencyWeighting::A.response(4000.0).decibel_value();
+            let max = meter.max_level().unwrap().decibel_value();
+            assert!((max - steady - expected).abs() <= 0.5,
+                    "{:?} burst of {} s: {} dB", time_weighting, duration, max - steady);
+        }
+    }
+
+    #[test]
+    fn test_steady_tones_read_the_same_on_every_time_weighting() {
+        // At low frequencies, the peak detector of Impulse holds the ripple
+        // that the 35 ms average leaves, so these start at 250 Hz.
+        for &frequency in [250.0, 1000.0, 8000.0].iter() {
+            let mut equivalent = None;
+            for &time_weighting in [TimeWeighting::Fast, TimeWeighting::Slow, TimeWeighting::Impulse].iter() {
+                let mut meter = meter(FrequencyWeighting::Z, time_weighting);
+                meter.process(&tone(frequency, 1.0, 8.0));
+                let equivalent = *equivalent.get_or_insert(meter.equivalent_level().decibel_value());
+                assert!((meter.level().decibel_value() - equivalent).abs() <= 0.1,
+                        "{:?} reads {:?} for {} Hz", time_weighting, meter.level(), frequency);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decay_rates() {
+        // Fast decays at 34.7 dB/s, Slow at 4.3 dB/s and Impulse at 2.9 dB/s.
+        let cases = [(TimeWeighting::Fast, 34.7), (TimeWeighting::Slow, 4.3), (TimeWeighting::Impulse, 2.9)];
+        for &(time_weighting, rate) in cases.iter() {
+            let mut meter = meter(FrequencyWeighting::Z, time_weighting);
+            meter.process(&tone(1000.0, 1.0, 6.0));
+            meter.process(&silence(0.1));
+            let before = meter.level().decibel_value();
+            meter.process(&silence(0.2));
+            let after = meter.level().decibel_value();
+            assert!(((before - after) / 0.2 - rate).abs() < 0.1,
+                    "{:?} decays at {} dB/s", time_weighting, (before - after) / 0.2);
+        }
+    }
+
+    #[test]
+    fn test_equivalent_and_exposure_levels() {
+        let mut meter = meter(FrequencyWeighting::Z, TimeWeighting::Fast);
+        assert_eq!(meter.equivalent_level(), DbSpl::new(f64::NEG_INFINITY));
+        assert_eq!(meter.max_level(), None);
+
+        // One second at 100 dB SPL, then one second of silence.
+        meter.process(&tone(1000.0, 1.0, 1.0));
+        assert_close(meter.exposure_level(), 100.0, 0.01);
+        meter.process(&silence(1.0));
+        assert_eq!(meter.duration(), 2.0);
+        assert_close(meter.equivalent_level(), 96.99, 0.01);
+        assert_close(meter.exposure_level(), 100.0, 0.01);
+    }
+
+    #[test]
+    fn test_max_and_min_levels() {
+        let mut meter = meter(FrequencyWeighting::C, TimeWeighting::Fast);
+        meter.process(&tone(1000.0, 1.0, 2.0));
+        meter.process(&tone(1000.0, 0.1, 2.0));
+        meter.process(&tone(1000.0, 1.0, 2.0));
+        assert_close(meter.max_level().unwrap(), 100.0, 0.1);
+        assert_close(meter.min_level().unwrap(), 80.0, 0.1);
+
+        meter.reset();
+        assert_eq!(meter.max_level(), None);
+        assert_eq!(meter.min_level(), None);
+        assert_eq!(meter.peak_level(), DbSpl::new(f64::NEG_INFINITY));
+        assert_eq!(meter.frequency_weighting(), FrequencyWeighting::C);
+        assert_eq!(meter.time_weighting(), TimeWeighting::Fast);
+    }
+
+    #[test]
+    fn test_integer_samples() {
+        let mut meter = meter(FrequencyWeighting::Z, TimeWeighting::Slow);
+        let samples: Vec<i16> = tone(1000.0, 0.5, 1.0).iter()
+            .map(|&sample| i16::from_normalized(sample, FullScale::Positive))
+            .collect();
+        meter.process(&samples);
+        assert_close(meter.equivalent_level(), 93.98, 0.01);
+    }
+}
@@ -0,0 +1,420 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics of a series of level readings.
+//!
+//! `LevelStatistics` accumulates readings taken at a fixed interval, such as
+//! the LAF of a sound level meter every 100 ms, into a histogram. It answers
+//! percentile queries, including exceedance levels like LA10 and LA90, from
+//! the histogram. The histogram covers a fixed range of levels, with an
+//! underflow and an overflow count for readings outside it, so its memory use
+//! depends only on the range and the bin width, and not on the readings.
+//!
+//! There are two different averages of decibel values:
+//!
+//! * `energetic_mean()` averages the underlying powers and converts the
+//!   result back to decibels. This is what Leq is, and what an average level
+//!   should usually be.
+//! * `arithmetic_mean()` averages the decibel values themselves. It is always
+//!   lower than the energetic mean, unless all the readings are the same.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::statistics::LevelStatistics;
+//!
+//! fn main() {
+//!     let mut statistics = LevelStatistics::new(1.0, 0.0..140.0, 0.1);
+//!     statistics.push(DecibelRatio(60.0));
+//!     statistics.push(DecibelRatio(70.0));
+//!
+//!     let energetic = statistics.energetic_mean().unwrap().decibel_value();
+//!     let arithmetic = statistics.arithmetic_mean().unwrap().decibel_value();
+//!     assert!(energetic > 67.39 && energetic < 67.41);
+//!     assert_eq!(arithmetic, 65.0);
+//! }
+//! ```
+
+use core::ops::Range;
+use std::vec::Vec;
+
+use {DecibelRatio, PowerRatio};
+
+// The most bins a histogram can have, which keeps a mistaken range from
+// taking all the memory.
+const MAX_BINS: usize = 1 << 20;
+
+/// A histogram of level readings taken at a fixed interval.
+///
+/// Readings below the histogram's range are counted as underflow, and
+/// readings at or above the top of it as overflow. Percentiles that fall
+/// among them are interpolated between the edge of the range and the lowest
+/// or highest reading. Readings of negative infinity, such as digital
+/// silence, are counted separately, and sort below every other reading.
+#[derive(Clone, Debug)]
+pub struct LevelStatistics {
+    interval: f64,
+    range: Range<f64>,
+    bin_width: f64,
+    // Bin `i` covers the levels from `range.start + i * bin_width` up to the
+    // next bin.
+    bins: Vec<u64>,
+    underflow: u64,
+    overflow: u64,
+    silent: u64,
+    count: u64,
+    power_sum: f64,
+    decibel_sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl LevelStatistics {
+    /// Creates empty statistics for readings taken every `interval` seconds,
+    /// with a histogram that covers `range` in bins `bin_width` decibels
+    /// wide. The last bin is narrower if the range isn't a whole number of
+    /// bins.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval or the bin width isn't positive, if the range
+    /// isn't finite and non-empty, or if it would take more than 2^20 bins.
+    pub fn new(interval: f64, range: Range<f64>, bin_width: f64) -> LevelStatistics {
+        assert!(interval > 0.0, "the interval must be positive");
+        assert!(bin_width > 0.0, "the bin width must be positive");
+        assert!(range.start.is_finite() && range.end.is_finite() && range.start < range.end,
+                "the range must be finite and not empty");
+        let bins = ((range.end - range.start) / bin_width).ceil();
+        assert!(bins <= MAX_BINS as f64, "the histogram must have at most 2^20 bins");
+        LevelStatistics {
+            interval,
+            range,
+            bin_width,
+            bins: vec![0; bins as usize],
+            underflow: 0,
+            overflow: 0,
+            silent: 0,
+            count: 0,
+            power_sum: 0.0,
+            decibel_sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Returns the width of the histogram bins, in decibels.
+    #[inline]
+    pub fn bin_width(&self) -> f64 {
+        self.bin_width
+    }
+
+    /// Returns the range of levels that the histogram covers.
+    #[inline]
+    pub fn range(&self) -> Range<f64> {
+        self.range.clone()
+    }
+
+    /// Adds a reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reading is NaN or positive infinity.
+    pub fn push(&mut self, level: DecibelRatio<f64>) {
+        let decibels = level.decibel_value();
+        assert!(decibels.is_finite() || decibels == f64::NEG_INFINITY,
+                "a reading must be finite or negative infinity");
+
+        self.count += 1;
+        self.power_sum += PowerRatio::from(level).power_value();
+        self.decibel_sum += decibels;
+        self.min = self.min.min(decibels);
+        self.max = self.max.max(decibels);
+        if decibels == f64::NEG_INFINITY {
+            self.silent += 1;
+            return;
+        }
+
+        if decibels < self.range.start {
+            self.underflow += 1;
+        } else if decibels >= self.range.end {
+            self.overflow += 1;
+        } else {
+            // Rounding can put a reading just under the end past the last bin.
+            let index = ((decibels - self.range.start) / self.bin_width) as usize;
+            let last = self.bins.len() - 1;
+            self.bins[index.min(last)] += 1;
+        }
+    }
+
+    /// Returns the number of readings below the histogram's range, not
+    /// counting readings of negative infinity.
+    #[inline]
+    pub fn underflow(&self) -> u64 {
+        self.underflow
+    }
+
+    /// Returns the number of readings at or above the top of the histogram's
+    /// range.
+    #[inline]
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// Returns the number of readings.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the time covered by the readings, in seconds.
+    #[inline]
+    pub fn duration(&self) -> f64 {
+        self.count as f64 * self.interval
+    }
+
+    /// Returns the lowest reading, or `None` if there are no readings.
+    pub fn min(&self) -> Option<DecibelRatio<f64>> {
+        if self.count == 0 { None } else { Some(DecibelRatio(self.min)) }
+    }
+
+    /// Returns the highest reading, or `None` if there are no readings.
+    pub fn max(&self) -> Option<DecibelRatio<f64>> {
+        if self.count == 0 { None } else { Some(DecibelRatio(self.max)) }
+    }
+
+    /// Returns the energetic mean of the readings: the mean of their powers,
+    /// in decibels. For sound levels, this is the equivalent continuous
+    /// level over the readings.
+    pub fn energetic_mean(&self) -> Option<DecibelRatio<f64>> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(PowerRatio(self.power_sum / self.count as f64).into())
+    }
+
+    /// Returns the arithmetic mean of the readings' decibel values. This is
+    /// negative infinity if any reading is.
+    pub fn arithmetic_mean(&self) -> Option<DecibelRatio<f64>> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(DecibelRatio(self.decibel_sum / self.count as f64))
+    }
+
+    /// Returns the level that `percent` percent of the readings are at or
+    /// below, interpolated within the histogram bin, or `None` if there are
+    /// no readings.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percent` isn't between 0 and 100.
+    pub fn percentile(&self, percent: f64) -> Option<DecibelRatio<f64>> {
+        assert!((0.0..=100.0).contains(&percent), "the percentile must be between 0 and 100");
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = percent / 100.0 * self.count as f64;
+        if self.silent > 0 && rank <= self.silent as f64 {
+            return Some(DecibelRatio(f64::NEG_INFINITY));
+        }
+        // The underflow and the overflow are like bins that reach from the
+        // edge of the range to the lowest and highest readings.
+        let underflow = (self.min, self.range.start, self.underflow);
+        let overflow = (self.range.end, self.max, self.overflow);
+        let bins = self.bins.iter().enumerate().map(|(index, &count)| {
+            let lower = self.bin_edge(index);
+            (lower, self.bin_edge(index + 1), count)
+        });
+
+        let mut below = self.silent as f64;
+        for (lower, upper, count) in Some(underflow).into_iter().chain(bins).chain(Some(overflow)) {
+            if count == 0 {
+                continue;
+            }
+            let count = count as f64;
+            if below + count >= rank {
+                // This form stays accurate when the underflow or the overflow
+                // reaches out to huge levels.
+                let fraction = (rank - below) / count;
+                let level = (1.0 - fraction) * lower + fraction * upper;
+                return Some(DecibelRatio(level.max(self.min).min(self.max)));
+            }
+            below += count;
+        }
+        Some(DecibelRatio(self.max))
+    }
+
+    // Returns the lower edge of a bin, or the end of the range past the last.
+    fn bin_edge(&self, index: usize) -> f64 {
+        (self.range.start + index as f64 * self.bin_width).min(self.range.end)
+    }
+
+    /// Returns the level that is exceeded by `percent` percent of the
+    /// readings, such as LA10 for `percent` = 10, or `None` if there are no
+    /// readings.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percent` isn't between 0 and 100.
+    pub fn exceedance_level(&self, percent: f64) -> Option<DecibelRatio<f64>> {
+        assert!((0.0..=100.0).contains(&percent), "the percentile must be between 0 and 100");
+        self.percentile(100.0 - percent)
+    }
+
+    /// Returns the histogram as the lower edge of each bin and the number of
+    /// readings in it, from the lowest bin to the highest. Readings outside
+    /// the range and readings of negative infinity aren't included.
+    pub fn histogram<'a>(&'a self) -> impl Iterator<Item = (DecibelRatio<f64>, u64)> + 'a {
+        self.bins.iter().enumerate().map(move |(index, &count)| (DecibelRatio(self.bin_edge(index)), count))
+    }
+
+    /// Forgets all readings.
+    pub fn reset(&mut self) {
+        *self = LevelStatistics::new(self.interval, self.range.clone(), self.bin_width);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(actual: Option<DecibelRatio<f64>>, expected: f64, tolerance: f64) {
+        let actual = actual.unwrap().decibel_value();
+        assert!((actual - expected).abs() <= tolerance,
+                "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn test_empty() {
+        let statistics = LevelStatistics::new(0.1, 0.0..140.0, 1.0);
+        assert_eq!(statistics.count(), 0);
+        assert_eq!(statistics.percentile(50.0), None);
+        assert_eq!(statistics.energetic_mean(), None);
+        assert_eq!(statistics.arithmetic_mean(), None);
+        assert_eq!(statistics.min(), None);
+        assert!(statistics.histogram().all(|(_, count)| count == 0));
+    }
+
+    #[test]
+    fn test_percentiles_of_a_uniform_distribution() {
+        // 10 s of readings every 10 ms, spread evenly from 40 to 80 dB.
+        let mut statistics = LevelStatistics::new(0.01, 20.0..100.0, 0.5);
+        for i in 0..1000 {
+            statistics.push(DecibelRatio(40.0 + 40.0 * (i as f64 + 0.5) / 1000.0));
+        }
+        assert_eq!(statistics.duration(), 10.0);
+        assert_close(statistics.percentile(50.0), 60.0, 0.05);
+        assert_close(statistics.exceedance_level(10.0), 76.0, 0.05);
+        assert_close(statistics.exceedance_level(90.0), 44.0, 0.05);
+        assert_close(statistics.percentile(0.0), 40.02, 0.001);
+        assert_close(statistics.percentile(100.0), 79.98, 0.001);
+        assert_eq!(statistics.histogram().count(), 160);
+        let used: Vec<u64> = statistics.histogram().map(|(_, count)| count).filter(|&count| count > 0).collect();
+        assert_eq!(used.len(), 80);
+        assert!(used.iter().all(|&count| count == 12 || count == 13));
+    }
+
+    #[test]
+    fn test_histogram_bins() {
+        let mut statistics = LevelStatistics::new(1.0, -12.0..7.0, 2.0);
+        for &level in [-3.0, 5.0, -12.0, 5.5, 6.999].iter() {
+            statistics.push(DecibelRatio(level));
+        }
+        let histogram: Vec<(DecibelRatio<f64>, u64)> = statistics.histogram().collect();
+        assert_eq!(histogram.len(), 10);
+        assert_eq!(histogram[0], (DecibelRatio(-12.0), 1));
+        assert_eq!(histogram[4], (DecibelRatio(-4.0), 1));
+        assert_eq!(histogram[8], (DecibelRatio(4.0), 2));
+        // The last bin is cut short by the end of the range.
+        assert_eq!(histogram[9], (DecibelRatio(6.0), 1));
+        assert_eq!(statistics.underflow() + statistics.overflow(), 0);
+    }
+
+    #[test]
+    fn test_readings_outside_the_range() {
+        let mut statistics = LevelStatistics::new(1.0, 40.0..80.0, 1.0);
+        statistics.push(DecibelRatio(-1e300));
+        statistics.push(DecibelRatio(0.0));
+        statistics.push(DecibelRatio(1e9));
+        statistics.push(DecibelRatio(80.0));
+        for i in 0..6 {
+            statistics.push(DecibelRatio(50.0 + i as f64));
+        }
+        assert_eq!(statistics.count(), 10);
+        assert_eq!(statistics.underflow(), 2);
+        assert_eq!(statistics.overflow(), 2);
+        assert_eq!(statistics.histogram().map(|(_, count)| count).sum::<u64>(), 6);
+        assert_eq!(statistics.min(), Some(DecibelRatio(-1e300)));
+        assert_eq!(statistics.max(), Some(DecibelRatio(1e9)));
+
+        // Percentiles among the readings outside the range interpolate out to
+        // the lowest and highest readings.
+        assert_eq!(statistics.percentile(0.0), Some(DecibelRatio(-1e300)));
+        assert_close(statistics.percentile(20.0), 40.0, 1e-9);
+        assert_close(statistics.percentile(50.0), 53.0, 1e-9);
+        assert_close(statistics.percentile(80.0), 56.0, 1e-9);
+        assert_close(statistics.percentile(90.0), 500_000_040.0, 1e-3);
+        assert_eq!(statistics.percentile(100.0), Some(DecibelRatio(1e9)));
+
+        statistics.reset();
+        assert_eq!(statistics.underflow(), 0);
+        assert_eq!(statistics.overflow(), 0);
+        assert_eq!(statistics.range(), 40.0..80.0);
+    }
+
+    #[test]
+    fn test_energetic_and_arithmetic_means() {
+        let mut statistics = LevelStatistics::new(1.0, 0.0..140.0, 0.1);
+        statistics.push(DecibelRatio(60.0));
+        statistics.push(DecibelRatio(60.0));
+        assert_close(statistics.energetic_mean(), 60.0, 1e-9);
+        assert_close(statistics.arithmetic_mean(), 60.0, 1e-9);
+
+        statistics.push(DecibelRatio(80.0));
+        statistics.push(DecibelRatio(80.0));
+        assert_close(statistics.energetic_mean(), 77.03, 0.01);
+        assert_close(statistics.arithmetic_mean(), 70.0, 1e-9);
+    }
+
+    #[test]
+    fn test_silent_readings() {
+        let mut statistics = LevelStatistics::new(1.0, 0.0..140.0, 1.0);
+        statistics.push(DecibelRatio(f64::NEG_INFINITY));
+        statistics.push(DecibelRatio(50.0));
+        assert_eq!(statistics.percentile(25.0), Some(DecibelRatio(f64::NEG_INFINITY)));
+        assert_close(statistics.percentile(100.0), 50.0, 1e-9);
+        assert_close(statistics.energetic_mean(), 46.99, 0.01);
+        assert_eq!(statistics.arithmetic_mean(), Some(DecibelRatio(f64::NEG_INFINITY)));
+        assert_eq!(statistics.histogram().map(|(_, count)| count).sum::<u64>(), 1);
+        assert_eq!(statistics.underflow(), 0);
+
+        statistics.reset();
+        assert_eq!(statistics.count(), 0);
+        assert_eq!(statistics.bin_width(), 1.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nan_readings_panic() {
+        LevelStatistics::new(1.0, 0.0..140.0, 1.0).push(DecibelRatio(f64::NAN));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_many_bins_panic() {
+        LevelStatistics::new(1.0, -1e300..1e300, 1.0);
+    }
+}
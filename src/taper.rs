@@ -0,0 +1,335 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Taper laws that map a fader or knob position to a gain.
+//!
+//! A position runs from 0, at the bottom of the control's travel, to 1, at
+//! the top. Every taper maps positions to gains and back again, so a host can
+//! store automation as gains and recover the same positions:
+//!
+//! * `LinearTaper` is linear in decibels, with an optional mute zone at the
+//!   bottom.
+//! * `AudioTaper` follows a power law in amplitude, like an audio-taper
+//!   potentiometer, and reaches silence at the bottom.
+//! * `BreakpointTaper` is linear in decibels between user-defined points, and
+//!   `CONSOLE_FADER` has the points of a typical mixing-console fader scale.
+//!
+//! Positions outside 0 to 1 are clamped, and so are gains outside the
+//! taper's range. A NaN position or gain gives NaN.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::DecibelRatio;
+//! use decibel::taper::{BreakpointTaper, Taper, CONSOLE_FADER};
+//!
+//! fn main() {
+//!     let fader = BreakpointTaper::new(&CONSOLE_FADER);
+//!     assert_eq!(fader.to_decibels(0.75), DecibelRatio(0.0));
+//!     assert_eq!(fader.to_position(DecibelRatio(-10.0)), 0.5);
+//! }
+//! ```
+
+use math;
+use {AmplitudeRatio, DecibelRatio};
+
+/// A mapping between control positions, from 0 to 1, and gains.
+pub trait Taper {
+    /// Returns the gain at a position.
+    fn to_decibels(&self, position: f64) -> DecibelRatio<f64>;
+
+    /// Returns the position of a gain. This is the inverse of
+    /// `to_decibels()`, up to rounding, for gains within the taper's range.
+    fn to_position(&self, gain: DecibelRatio<f64>) -> f64;
+}
+
+#[inline]
+fn clamp_position(position: f64) -> f64 {
+    position.clamp(0.0, 1.0)
+}
+
+/// A taper that is linear in decibels from `min` at the bottom of the range
+/// to `max` at the top, with positions below the mute zone muted.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LinearTaper {
+    min: f64,
+    max: f64,
+    mute_zone: f64,
+}
+
+impl LinearTaper {
+    /// Creates a linear taper between two gains in dB. Positions below
+    /// `mute_zone` are muted, and the linear range starts there. A mute zone
+    /// of zero never mutes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` isn't below `max`, or if the mute zone isn't between 0
+    /// and 1.
+    pub fn new(min: f64, max: f64, mute_zone: f64) -> LinearTaper {
+        assert!(min < max, "the minimum gain must be below the maximum");
+        assert!((0.0..1.0).contains(&mute_zone), "the mute zone must be between 0 and 1");
+        LinearTaper { min, max, mute_zone }
+    }
+}
+
+impl Taper for LinearTaper {
+    fn to_decibels(&self, position: f64) -> DecibelRatio<f64> {
+        let position = clamp_position(position);
+        if position < self.mute_zone {
+            return DecibelRatio(f64::NEG_INFINITY);
+        }
+        let fraction = (position - self.mute_zone) / (1.0 - self.mute_zone);
+        DecibelRatio(self.min + fraction * (self.max - self.min))
+    }
+
+    fn to_position(&self, gain: DecibelRatio<f64>) -> f64 {
+        let decibels = gain.decibel_value();
+        if decibels == f64::NEG_INFINITY && self.mute_zone > 0.0 {
+            return 0.0;
+        }
+        let fraction = ((decibels - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        self.mute_zone + fraction * (1.0 - self.mute_zone)
+    }
+}
+
+/// A taper that follows a power law in amplitude, like an audio-taper
+/// potentiometer: the amplitude is the position raised to `exponent`, scaled
+/// so that the top of the range is at `max`.
+///
+/// With an exponent of 3, the bottom half of the travel covers everything
+/// below 18 dB under the maximum.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AudioTaper {
+    exponent: f64,
+    max: f64,
+}
+
+impl AudioTaper {
+    /// Creates an audio taper with the given exponent and maximum gain in dB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the exponent isn't positive.
+    pub fn new(exponent: f64, max: f64) -> AudioTaper {
+        assert!(exponent > 0.0, "the exponent must be positive");
+        AudioTaper { exponent, max }
+    }
+}
+
+impl Taper for AudioTaper {
+    fn to_decibels(&self, position: f64) -> DecibelRatio<f64> {
+        let relative: DecibelRatio<f64> = AmplitudeRatio(clamp_position(position)).into();
+        relative * self.exponent + DecibelRatio(self.max)
+    }
+
+    fn to_position(&self, gain: DecibelRatio<f64>) -> f64 {
+        let relative = (gain - DecibelRatio(self.max)) / self.exponent;
+        clamp_position(AmplitudeRatio::from(relative).amplitude_value())
+    }
+}
+
+/// The points of a typical mixing-console fader scale, as positions and
+/// gains in dB, from silence at the bottom to +10 dB at the top, with unity
+/// gain three quarters of the way up.
+pub const CONSOLE_FADER: [(f64, f64); 10] = [
+    (0.0, f64::NEG_INFINITY),
+    (0.05, -60.0),
+    (0.15, -40.0),
+    (0.25, -30.0),
+    (0.35, -20.0),
+    (0.5, -10.0),
+    (0.625, -5.0),
+    (0.75, 0.0),
+    (0.875, 5.0),
+    (1.0, 10.0),
+];
+
+/// A taper made of segments between points, each given as a position and a
+/// gain in dB.
+///
+/// Segments are linear in decibels. A segment that starts at negative
+/// infinity can't be, so it is linear in amplitude instead, which fades out
+/// smoothly to silence.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BreakpointTaper<'a> {
+    points: &'a [(f64, f64)],
+}
+
+impl<'a> BreakpointTaper<'a> {
+    /// Creates a taper from its points. Positions before the first point or
+    /// after the last one take the gain of that point.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two points, if the positions aren't
+    /// between 0 and 1, or if the positions and gains don't both strictly
+    /// increase. Only the first point's gain may be negative infinity.
+    pub fn new(points: &'a [(f64, f64)]) -> BreakpointTaper<'a> {
+        assert!(points.len() >= 2, "a taper needs at least two points");
+        for (index, &(position, gain)) in points.iter().enumerate() {
+            assert!((0.0..=1.0).contains(&position), "positions must be between 0 and 1");
+            assert!(gain.is_finite() || (index == 0 && gain == f64::NEG_INFINITY),
+                    "only the first gain may be negative infinity");
+        }
+        for pair in points.windows(2) {
+            assert!(pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1,
+                    "positions and gains must strictly increase");
+        }
+        BreakpointTaper { points }
+    }
+}
+
+impl<'a> Taper for BreakpointTaper<'a> {
+    fn to_decibels(&self, position: f64) -> DecibelRatio<f64> {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if position.is_nan() {
+            return DecibelRatio(f64::NAN);
+        }
+        if position <= first.0 {
+            return DecibelRatio(first.1);
+        }
+        if position >= last.0 {
+            return DecibelRatio(last.1);
+        }
+
+        let segment = self.points.windows(2).find(|pair| position < pair[1].0).unwrap();
+        let ((start, low), (end, high)) = (segment[0], segment[1]);
+        let fraction = (position - start) / (end - start);
+        if low == f64::NEG_INFINITY {
+            let high = AmplitudeRatio::from(DecibelRatio(high)).amplitude_value();
+            AmplitudeRatio(fraction * high).into()
+        } else {
+            DecibelRatio(low + fraction * (high - low))
+        }
+    }
+
+    fn to_position(&self, gain: DecibelRatio<f64>) -> f64 {
+        let decibels = gain.decibel_value();
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if decibels.is_nan() {
+            return f64::NAN;
+        }
+        if decibels <= first.1 {
+            return first.0;
+        }
+        if decibels >= last.1 {
+            return last.0;
+        }
+
+        let segment = self.points.windows(2).find(|pair| decibels < pair[1].1).unwrap();
+        let ((start, low), (end, high)) = (segment[0], segment[1]);
+        let fraction = if low == f64::NEG_INFINITY {
+            math::pow(10.0, (decibels - high) / 20.0)
+        } else {
+            (decibels - low) / (high - low)
+        };
+        start + fraction * (end - start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_round_trip<T: Taper>(taper: &T, position: f64) {
+        let gain = taper.to_decibels(position);
+        let back = taper.to_position(gain);
+        assert!((back - position).abs() < 1e-12, "{} became {:?} and then {}", position, gain, back);
+        let again = taper.to_decibels(back).decibel_value();
+        assert!((again - gain.decibel_value()).abs() < 1e-9 || again == gain.decibel_value());
+    }
+
+    #[test]
+    fn test_linear_taper() {
+        let taper = LinearTaper::new(-60.0, 0.0, 0.0);
+        assert_eq!(taper.to_decibels(0.0), DecibelRatio(-60.0));
+        assert_eq!(taper.to_decibels(0.5), DecibelRatio(-30.0));
+        assert_eq!(taper.to_decibels(1.5), DecibelRatio(0.0));
+        assert_eq!(taper.to_position(DecibelRatio(-90.0)), 0.0);
+
+        let taper = LinearTaper::new(-70.0, 10.0, 0.2);
+        assert_eq!(taper.to_decibels(0.1), DecibelRatio(f64::NEG_INFINITY));
+        assert_eq!(taper.to_decibels(0.2), DecibelRatio(-70.0));
+        assert!((taper.to_decibels(0.6).decibel_value() + 30.0).abs() < 1e-9);
+        assert_eq!(taper.to_position(DecibelRatio(f64::NEG_INFINITY)), 0.0);
+        assert_eq!(taper.to_position(DecibelRatio(-100.0)), 0.2);
+        for i in 20..=100 {
+            assert_round_trip(&taper, i as f64 / 100.0);
+        }
+    }
+
+    #[test]
+    fn test_audio_taper() {
+        let taper = AudioTaper::new(3.0, 6.0);
+        assert_eq!(taper.to_decibels(1.0), DecibelRatio(6.0));
+        assert_eq!(taper.to_decibels(0.0), DecibelRatio(f64::NEG_INFINITY));
+        assert!((taper.to_decibels(0.5).decibel_value() - (6.0 - 18.0618)).abs() < 1e-4);
+        assert_eq!(taper.to_position(DecibelRatio(f64::NEG_INFINITY)), 0.0);
+        assert_eq!(taper.to_position(DecibelRatio(12.0)), 1.0);
+        for i in 1..=100 {
+            assert_round_trip(&taper, i as f64 / 100.0);
+        }
+    }
+
+    #[test]
+    fn test_console_fader() {
+        let fader = BreakpointTaper::new(&CONSOLE_FADER);
+        for &(position, gain) in CONSOLE_FADER.iter() {
+            assert_eq!(fader.to_decibels(position), DecibelRatio(gain));
+            assert_eq!(fader.to_position(DecibelRatio(gain)), position);
+        }
+        assert!((fader.to_decibels(0.3).decibel_value() + 25.0).abs() < 1e-9);
+        // The bottom segment fades out linearly in amplitude.
+        let quarter: DecibelRatio<f64> = AmplitudeRatio(0.001 / 4.0).into();
+        assert!((fader.to_decibels(0.0125).decibel_value() - quarter.decibel_value()).abs() < 1e-9);
+        assert_eq!(fader.to_position(DecibelRatio(20.0)), 1.0);
+        for i in 0..=100 {
+            assert_round_trip(&fader, i as f64 / 100.0);
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_taper() {
+        let points = [(0.1, -40.0), (0.9, 0.0)];
+        let taper = BreakpointTaper::new(&points);
+        assert_eq!(taper.to_decibels(0.0), DecibelRatio(-40.0));
+        assert!((taper.to_decibels(0.5).decibel_value() + 20.0).abs() < 1e-9);
+        assert_eq!(taper.to_decibels(1.0), DecibelRatio(0.0));
+        assert!((taper.to_position(DecibelRatio(-10.0)) - 0.7).abs() < 1e-12);
+        for i in 10..=90 {
+            assert_round_trip(&taper, i as f64 / 100.0);
+        }
+    }
+
+    #[test]
+    fn test_nan_passes_through() {
+        let linear = LinearTaper::new(-60.0, 10.0, 0.0);
+        let audio = AudioTaper::new(3.0, 0.0);
+        let fader = BreakpointTaper::new(&CONSOLE_FADER);
+        let tapers: [&dyn Taper; 3] = [&linear, &audio, &fader];
+        for taper in tapers.iter() {
+            assert!(taper.to_decibels(f64::NAN).decibel_value().is_nan());
+            assert!(taper.to_position(DecibelRatio(f64::NAN)).is_nan());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_breakpoints_must_increase() {
+        BreakpointTaper::new(&[(0.0, -20.0), (0.5, -30.0), (1.0, 0.0)]);
+    }
+}
@@ -0,0 +1,285 @@
+// Decibel -- Quick conversion utilities for decibel values.
+// Copyright (c) 2016 Kevin Brothaler and the Decibel project authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// A copy of the License has been included in the root of the repository.
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Frequency weightings as specified by IEC 61672-1.
+//!
+//! `a_weighting_db()` and `c_weighting_db()` return the weightings' exact
+//! magnitude response at a frequency, from the closed forms in the standard.
+//! `WeightingFilter` applies a weighting to a stream of samples, with IIR
+//! filters designed for the sample rate.
+//!
+//! The filters are made by the bilinear transform of the analog weightings.
+//! They follow the exact response closely through the midrange, and fall
+//! below it towards the Nyquist frequency: at 48 kHz, A-weighting is 1.2 dB
+//! low at 10 kHz and 6.4 dB low at 16 kHz. This stays within the class 1
+//! tolerances of IEC 61672-1 at sample rates of 44.1 kHz and above.
+//!
+//! ## Example
+//!
+//! ```rust
+//! extern crate decibel;
+//!
+//! use decibel::weighting::{a_weighting_db, FrequencyWeighting, WeightingFilter};
+//!
+//! fn main() {
+//!     let response = a_weighting_db(100.0).decibel_value();
+//!     assert!(response > -19.2 && response < -19.1);
+//!
+//!     let mut filter = WeightingFilter::new(FrequencyWeighting::A, 48000.0);
+//!     let mut samples = vec![0.0; 480];
+//!     samples[0] = 1.0;
+//!     filter.process_in_place(&mut samples);
+//! }
+//! ```
+
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+use biquad::Biquad;
+use {AmplitudeRatio, DecibelRatio};
+
+// The pole frequencies of the weightings, in Hz, from IEC 61672-1.
+const F1: f64 = 20.598_997_057_568_145;
+const F2: f64 = 107.652_648_643_046_29;
+const F3: f64 = 737.862_230_736_290_1;
+const F4: f64 = 12_194.217_148_271_01;
+
+// The normalization constants that make the weightings 0 dB at 1 kHz.
+const A_1000: f64 = -2.000;
+const C_1000: f64 = -0.062;
+
+/// A frequency weighting from IEC 61672-1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrequencyWeighting {
+    /// A-weighting, which approximates the ear's sensitivity at low levels.
+    A,
+    /// C-weighting, which is nearly flat except at the extremes of the
+    /// audible range.
+    C,
+    /// Z-weighting, which is flat.
+    Z,
+}
+
+impl FrequencyWeighting {
+    /// Returns the weighting's exact magnitude response at a frequency in Hz.
+    pub fn response(self, frequency: f64) -> DecibelRatio<f64> {
+        match self {
+            FrequencyWeighting::A => a_weighting_db(frequency),
+            FrequencyWeighting::C => c_weighting_db(frequency),
+            FrequencyWeighting::Z => DecibelRatio(0.0),
+        }
+    }
+}
+
+/// Returns the A-weighting's magnitude response at a frequency in Hz.
+pub fn a_weighting_db(frequency: f64) -> DecibelRatio<f64> {
+    let f2 = frequency * frequency;
+    let ratio = F4 * F4 * f2 * f2
+        / ((f2 + F1 * F1) * ((f2 + F2 * F2) * (f2 + F3 * F3)).sqrt() * (f2 + F4 * F4));
+    DecibelRatio::from(AmplitudeRatio(ratio)) - DecibelRatio(A_1000)
+}
+
+/// Returns the C-weighting's magnitude response at a frequency in Hz.
+pub fn c_weighting_db(frequency: f64) -> DecibelRatio<f64> {
+    let f2 = frequency * frequency;
+    let ratio = F4 * F4 * f2 / ((f2 + F1 * F1) * (f2 + F4 * F4));
+    DecibelRatio::from(AmplitudeRatio(ratio)) - DecibelRatio(C_1000)
+}
+
+// Maps an analog second-order section, with coefficients for s², s and 1,
+// onto a biquad with the bilinear transform.
+fn bilinear(b: [f64; 3], a: [f64; 3], sample_rate: f64) -> Biquad {
+    let k = 2.0 * sample_rate;
+    let k2 = k * k;
+    Biquad::new(b[0] * k2 + b[1] * k + b[2],
+                2.0 * (b[2] - b[0] * k2),
+                b[0] * k2 - b[1] * k + b[2],
+                a[0] * k2 + a[1] * k + a[2],
+                2.0 * (a[2] - a[0] * k2),
+                a[0] * k2 - a[1] * k + a[2])
+}
+
+/// A filter that applies a frequency weighting to one channel of samples.
+#[derive(Clone, Debug)]
+pub struct WeightingFilter {
+    weighting: FrequencyWeighting,
+    sections: Vec<Biquad>,
+}
+
+impl WeightingFilter {
+    /// Creates a filter for a weighting at the given sample rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sample rate isn't positive.
+    pub fn new(weighting: FrequencyWeighting, sample_rate: f64) -> WeightingFilter {
+        assert!(sample_rate > 0.0, "the sample rate must be positive");
+        let w1 = 2.0 * PI * F1;
+        let w2 = 2.0 * PI * F2;
+        let w3 = 2.0 * PI * F3;
+        let w4 = 2.0 * PI * F4;
+
+        // Both weightings share the double poles at f1 and f4, and the
+        // A-weighting adds two more high-pass poles at f2 and f3.
+        let (gain, extra_high_pass) = match weighting {
+            FrequencyWeighting::A => (A_1000, true),
+            FrequencyWeighting::C => (C_1000, false),
+            FrequencyWeighting::Z => return WeightingFilter { weighting, sections: Vec::new() },
+        };
+        let gain = AmplitudeRatio::from(DecibelRatio(-gain)).amplitude_value();
+        let mut sections = vec![
+            bilinear([gain, 0.0, 0.0], [1.0, 2.0 * w1, w1 * w1], sample_rate),
+            bilinear([0.0, 0.0, w4 * w4], [1.0, 2.0 * w4, w4 * w4], sample_rate),
+        ];
+        if extra_high_pass {
+            sections.push(bilinear([1.0, 0.0, 0.0], [1.0, w2 + w3, w2 * w3], sample_rate));
+        }
+        WeightingFilter { weighting, sections }
+    }
+
+    /// Returns the weighting this filter applies.
+    pub fn weighting(&self) -> FrequencyWeighting {
+        self.weighting
+    }
+
+    /// Filters one sample.
+    #[inline]
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.sections.iter_mut().fold(input, |sample, section| section.process(sample))
+    }
+
+    /// Filters a block of samples in place.
+    pub fn process_in_place(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    /// Clears the filter's memory of previous samples.
+    pub fn reset(&mut self) {
+        for section in &mut self.sections {
+            section.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Nominal frequencies and the A- and C-weightings from table 3 of
+    // IEC 61672-1, which are given for the exact base-ten frequencies.
+    const TABLE: [(f64, f64, f64); 34] = [
+        (10.0, -70.4, -14.3), (12.5, -63.4, -11.2), (16.0, -56.7, -8.5),
+        (20.0, -50.5, -6.2), (25.0, -44.7, -4.4), (31.5, -39.4, -3.0),
+        (40.0, -34.6, -2.0), (50.0, -30.2, -1.3), (63.0, -26.2, -0.8),
+        (80.0, -22.5, -0.5), (100.0, -19.1, -0.3), (125.0, -16.1, -0.2),
+        (160.0, -13.4, -0.1), (200.0, -10.9, 0.0), (250.0, -8.6, 0.0),
+        (315.0, -6.6, 0.0), (400.0, -4.8, 0.0), (500.0, -3.2, 0.0),
+        (630.0, -1.9, 0.0), (800.0, -0.8, 0.0), (1000.0, 0.0, 0.0),
+        (1250.0, 0.6, 0.0), (1600.0, 1.0, -0.1), (2000.0, 1.2, -0.2),
+        (2500.0, 1.3, -0.3), (3150.0, 1.2, -0.5), (4000.0, 1.0, -0.8),
+        (5000.0, 0.5, -1.3), (6300.0, -0.1, -2.0), (8000.0, -1.1, -3.0),
+        (10000.0, -2.5, -4.4), (12500.0, -4.3, -6.2), (16000.0, -6.6, -8.5),
+        (20000.0, -9.3, -11.2),
+    ];
+
+    // Class 1 tolerance limits from IEC 61672-1, as (upper, lower) in dB.
+    const CLASS_1: [(f64, f64, f64); 11] = [
+        (31.5, 1.5, -1.5), (63.0, 1.0, -1.0), (125.0, 1.0, -1.0),
+        (250.0, 1.0, -1.0), (500.0, 1.0, -1.0), (1000.0, 0.7, -0.7),
+        (2000.0, 1.0, -1.0), (4000.0, 1.0, -1.0), (8000.0, 1.5, -2.5),
+        (12500.0, 2.0, -5.0), (16000.0, 2.5, -16.0),
+    ];
+
+    // Returns the exact base-ten frequency for a nominal one-third-octave
+    // frequency.
+    fn exact_frequency(nominal: f64) -> f64 {
+        1000.0 * 10f64.powf(((nominal / 1000.0).log10() * 10.0).round() / 10.0)
+    }
+
+    // Measures a filter's gain at a frequency by filtering a sine and
+    // comparing RMS levels over a whole number of periods.
+    fn measured_response(weighting: FrequencyWeighting, sample_rate: f64, frequency: f64) -> f64 {
+        let mut filter = WeightingFilter::new(weighting, sample_rate);
+        let settle = sample_rate as usize;
+        let periods = (frequency / 2.0).floor().max(1.0);
+        let length = (periods * sample_rate / frequency).round() as usize;
+        let mut sum = 0.0;
+        for i in 0..settle + length {
+            let input = (2.0 * PI * frequency * i as f64 / sample_rate).sin();
+            let output = filter.process(input);
+            if i >= settle {
+                sum += output * output;
+            }
+        }
+        10.0 * (2.0 * sum / length as f64).log10()
+    }
+
+    #[test]
+    fn test_closed_forms_match_the_table() {
+        // The table is rounded to 0.1 dB, and some values, like A-weighting
+        // at 160 Hz, sit right on the rounding boundary.
+        for &(nominal, a, c) in TABLE.iter() {
+            let frequency = exact_frequency(nominal);
+            let actual_a = a_weighting_db(frequency).decibel_value();
+            let actual_c = c_weighting_db(frequency).decibel_value();
+            assert!((actual_a - a).abs() <= 0.051, "A at {} Hz: {} != {}", nominal, actual_a, a);
+            assert!((actual_c - c).abs() <= 0.051, "C at {} Hz: {} != {}", nominal, actual_c, c);
+        }
+    }
+
+    #[test]
+    fn test_closed_forms_are_normalized_at_1_khz() {
+        assert!(a_weighting_db(1000.0).decibel_value().abs() < 0.001);
+        assert!(c_weighting_db(1000.0).decibel_value().abs() < 0.001);
+        assert_eq!(FrequencyWeighting::Z.response(1000.0), DecibelRatio(0.0));
+        assert_eq!(FrequencyWeighting::A.response(0.0), DecibelRatio(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_filters_are_within_class_1_tolerances() {
+        for &sample_rate in [44100.0, 48000.0, 96000.0].iter() {
+            for &weighting in [FrequencyWeighting::A, FrequencyWeighting::C, FrequencyWeighting::Z].iter() {
+                for &(nominal, upper, lower) in CLASS_1.iter() {
+                    let frequency = exact_frequency(nominal);
+                    let error = measured_response(weighting, sample_rate, frequency)
+                        - weighting.response(frequency).decibel_value();
+                    assert!(error <= upper && error >= lower,
+                            "{:?} at {} Hz and {} Hz: {} dB off", weighting, nominal, sample_rate, error);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_filters_follow_the_closed_forms_through_the_midrange() {
+        for &weighting in [FrequencyWeighting::A, FrequencyWeighting::C].iter() {
+            for &nominal in [31.5, 100.0, 315.0, 1000.0, 2000.0].iter() {
+                let frequency = exact_frequency(nominal);
+                let error = measured_response(weighting, 48000.0, frequency)
+                    - weighting.response(frequency).decibel_value();
+                assert!(error.abs() < 0.05, "{:?} at {} Hz: {} dB off", weighting, nominal, error);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reset_clears_state() {
+        let mut filter = WeightingFilter::new(FrequencyWeighting::A, 48000.0);
+        assert_eq!(filter.weighting(), FrequencyWeighting::A);
+        let first: Vec<f64> = (0..16).map(|i| filter.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        filter.reset();
+        let second: Vec<f64> = (0..16).map(|i| filter.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        assert_eq!(first, second);
+    }
+}